@@ -0,0 +1,302 @@
+// NetworkManager secret agent.
+//
+// NetworkManager asks registered agents for credentials when a connection it
+// activates on its own (autoconnect, re-auth) is missing secrets. We register on
+// the system bus and hand each request to the TUI, which answers it through the
+// regular password prompt.
+
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+
+use zbus::blocking::Connection;
+use zbus::fdo::DBusProxy;
+use zbus::interface;
+use zbus::message::Header;
+use zbus::names::BusName;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+use crate::error::Error;
+use crate::ssid::Ssid;
+
+const NM_SERVICE: &str = "org.freedesktop.NetworkManager";
+const AGENT_MANAGER_PATH: &str = "/org/freedesktop/NetworkManager/AgentManager";
+const AGENT_MANAGER_IFACE: &str = "org.freedesktop.NetworkManager.AgentManager";
+const AGENT_PATH: &str = "/org/freedesktop/NetworkManager/SecretAgent";
+const AGENT_IDENTIFIER: &str = "io.github.paterkleomenis.wifi_menu";
+
+// NMSecretAgentGetSecretsFlags
+const FLAG_ALLOW_INTERACTION: u32 = 0x1;
+
+type Settings = HashMap<String, HashMap<String, OwnedValue>>;
+type Reply = async_channel::Sender<Option<String>>;
+
+/// A pending secrets request from NetworkManager.
+pub struct SecretRequest {
+    pub ssid: Ssid,
+    pub setting_name: String,
+    /// Security label in the same form as `nmcli`'s SECURITY column, so the
+    /// password prompt can treat it like a scanned network.
+    pub security: String,
+    reply: Reply,
+}
+
+impl SecretRequest {
+    /// Answers the request. `None` tells NetworkManager the user cancelled.
+    pub fn respond(&self, secret: Option<String>) {
+        let _ = self.reply.send_blocking(secret);
+    }
+
+    /// True once NetworkManager has withdrawn the request (CancelGetSecrets or timeout).
+    pub fn is_cancelled(&self) -> bool {
+        self.reply.is_closed()
+    }
+}
+
+#[derive(Debug, zbus::DBusError)]
+#[zbus(prefix = "org.freedesktop.NetworkManager.SecretAgent")]
+enum AgentError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    NotAuthorized(String),
+    UserCanceled(String),
+    NoSecrets(String),
+}
+
+/// Only NetworkManager may ask for secrets. Anyone else on the system bus
+/// could otherwise pop up the password prompt and read back what was typed.
+async fn check_caller(connection: &zbus::Connection, header: &Header<'_>) -> Result<(), AgentError> {
+    let not_authorized = || AgentError::NotAuthorized("Only NetworkManager may ask for secrets".into());
+    let sender = header.sender().ok_or_else(not_authorized)?;
+    let dbus = DBusProxy::new(connection).await?;
+    let owner = dbus
+        .get_name_owner(BusName::try_from(NM_SERVICE).map_err(zbus::Error::from)?)
+        .await
+        .map_err(|_| not_authorized())?;
+    if owner.as_str() != sender.as_str() {
+        return Err(not_authorized());
+    }
+    Ok(())
+}
+
+struct Agent {
+    requests: Sender<SecretRequest>,
+    // Keyed by (connection path, setting name) so CancelGetSecrets can find the request
+    pending: Mutex<HashMap<(String, String), Reply>>,
+}
+
+#[interface(name = "org.freedesktop.NetworkManager.SecretAgent")]
+impl Agent {
+    #[allow(clippy::too_many_arguments)]
+    async fn get_secrets(
+        &self,
+        #[zbus(connection)] bus: &zbus::Connection,
+        #[zbus(header)] header: Header<'_>,
+        connection: Settings,
+        connection_path: OwnedObjectPath,
+        setting_name: String,
+        _hints: Vec<String>,
+        flags: u32,
+    ) -> Result<Settings, AgentError> {
+        check_caller(bus, &header).await?;
+        if flags & FLAG_ALLOW_INTERACTION == 0 {
+            return Err(AgentError::NoSecrets("wifi_menu only provides interactive secrets".into()));
+        }
+
+        let (security, key) = secret_kind(&connection, &setting_name).ok_or_else(|| {
+            AgentError::NoSecrets(format!("Unsupported setting '{}'", setting_name))
+        })?;
+
+        let (reply, answer) = async_channel::bounded(1);
+        let pending_key = (connection_path.to_string(), setting_name.clone());
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(pending_key.clone(), reply.clone());
+        }
+
+        let request = SecretRequest {
+            ssid: connection_ssid(&connection),
+            setting_name: setting_name.clone(),
+            security,
+            reply,
+        };
+        if self.requests.send(request).is_err() {
+            return Err(AgentError::NoSecrets("wifi_menu is shutting down".into()));
+        }
+
+        let secret = answer.recv().await.ok().flatten();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&pending_key);
+        }
+
+        let secret = secret.ok_or_else(|| AgentError::UserCanceled("Cancelled by user".into()))?;
+        let value = OwnedValue::try_from(Value::from(secret)).map_err(zbus::Error::from)?;
+        Ok(HashMap::from([(setting_name, HashMap::from([(key.to_string(), value)]))]))
+    }
+
+    async fn cancel_get_secrets(
+        &self,
+        #[zbus(connection)] bus: &zbus::Connection,
+        #[zbus(header)] header: Header<'_>,
+        connection_path: OwnedObjectPath,
+        setting_name: String,
+    ) -> Result<(), AgentError> {
+        check_caller(bus, &header).await?;
+        if let Ok(mut pending) = self.pending.lock() {
+            // Closing the channel wakes get_secrets and lets the TUI notice.
+            if let Some(reply) = pending.remove(&(connection_path.to_string(), setting_name)) {
+                reply.close();
+            }
+        }
+        Ok(())
+    }
+
+    // We never store secrets ourselves; NetworkManager keeps them in the profile.
+    async fn save_secrets(&self, _connection: Settings, _connection_path: OwnedObjectPath) {}
+
+    async fn delete_secrets(&self, _connection: Settings, _connection_path: OwnedObjectPath) {}
+}
+
+/// Registered agent. NetworkManager drops the registration when this is dropped
+/// and the bus connection closes.
+pub struct SecretAgent {
+    _connection: Connection,
+    requests: Receiver<SecretRequest>,
+}
+
+impl SecretAgent {
+    pub fn register() -> Result<Self, Error> {
+        let (tx, rx) = mpsc::channel();
+        let agent = Agent {
+            requests: tx,
+            pending: Default::default(),
+        };
+
+        let connection = zbus::blocking::connection::Builder::system()
+            .and_then(|b| b.serve_at(AGENT_PATH, agent))
+            .and_then(|b| b.build())
+            .map_err(|source| Error::DBus {
+                context: "Cannot connect to the system bus",
+                source: Box::new(source),
+            })?;
+
+        connection
+            .call_method(
+                Some(NM_SERVICE),
+                AGENT_MANAGER_PATH,
+                Some(AGENT_MANAGER_IFACE),
+                "Register",
+                &(AGENT_IDENTIFIER,),
+            )
+            .map_err(|source| Error::DBus {
+                context: "Cannot register secret agent",
+                source: Box::new(source),
+            })?;
+
+        Ok(Self {
+            _connection: connection,
+            requests: rx,
+        })
+    }
+
+    pub fn try_next(&self) -> Option<SecretRequest> {
+        self.requests.try_recv().ok()
+    }
+
+    pub fn next(&self) -> Option<SecretRequest> {
+        self.requests.recv().ok()
+    }
+}
+
+fn setting_str<'a>(connection: &'a Settings, setting: &str, key: &str) -> Option<&'a str> {
+    connection
+        .get(setting)
+        .and_then(|s| s.get(key))
+        .and_then(|v| <&str>::try_from(&**v).ok())
+}
+
+fn connection_ssid(connection: &Settings) -> Ssid {
+    let ssid = connection
+        .get("802-11-wireless")
+        .and_then(|s| s.get("ssid"))
+        .and_then(|v| match &**v {
+            Value::Array(bytes) => Some(
+                bytes
+                    .iter()
+                    .filter_map(|b| u8::try_from(b).ok())
+                    .collect::<Vec<u8>>(),
+            ),
+            _ => None,
+        });
+
+    match ssid {
+        Some(bytes) if !bytes.is_empty() => Ssid::from_bytes(bytes),
+        _ => Ssid::from(setting_str(connection, "connection", "id").unwrap_or("?")),
+    }
+}
+
+/// Maps the requested setting to a security label and the secret key NetworkManager expects.
+fn secret_kind(connection: &Settings, setting_name: &str) -> Option<(String, &'static str)> {
+    match setting_name {
+        "802-11-wireless-security" => {
+            match setting_str(connection, setting_name, "key-mgmt").unwrap_or("wpa-psk") {
+                // Dynamic WEP (ieee8021x) gets its keys from 802.1X, not from the user
+                "none" => Some(("WEP".to_string(), "wep-key0")),
+                "sae" => Some(("WPA3".to_string(), "psk")),
+                "wpa-psk" => Some(("WPA2".to_string(), "psk")),
+                _ => None,
+            }
+        }
+        "802-1x" => Some(("802.1X".to_string(), "password")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(entries: &[(&str, &str, Value<'_>)]) -> Settings {
+        let mut settings = Settings::new();
+        for (setting, key, value) in entries {
+            settings
+                .entry(setting.to_string())
+                .or_default()
+                .insert(key.to_string(), OwnedValue::try_from(value.try_clone().unwrap()).unwrap());
+        }
+        settings
+    }
+
+    fn key_mgmt(value: &str) -> Settings {
+        settings(&[("802-11-wireless-security", "key-mgmt", Value::from(value))])
+    }
+
+    #[test]
+    fn secret_kind_by_key_mgmt() {
+        let wireless = "802-11-wireless-security";
+        assert_eq!(secret_kind(&key_mgmt("wpa-psk"), wireless), Some(("WPA2".into(), "psk")));
+        assert_eq!(secret_kind(&key_mgmt("sae"), wireless), Some(("WPA3".into(), "psk")));
+        assert_eq!(secret_kind(&key_mgmt("none"), wireless), Some(("WEP".into(), "wep-key0")));
+        assert_eq!(secret_kind(&Settings::new(), wireless), Some(("WPA2".into(), "psk")));
+        assert_eq!(secret_kind(&key_mgmt("ieee8021x"), wireless), None);
+        assert_eq!(secret_kind(&key_mgmt("wpa-eap"), wireless), None);
+        assert_eq!(secret_kind(&Settings::new(), "802-1x"), Some(("802.1X".into(), "password")));
+        assert_eq!(secret_kind(&Settings::new(), "vpn"), None);
+    }
+
+    #[test]
+    fn connection_ssid_prefers_the_ssid_bytes() {
+        let connection = settings(&[
+            ("802-11-wireless", "ssid", Value::from(b"Caf\xE9".to_vec())),
+            ("connection", "id", Value::from("Cafe profile")),
+        ]);
+        assert_eq!(connection_ssid(&connection), Ssid::from_bytes(b"Caf\xE9"));
+
+        let empty = settings(&[
+            ("802-11-wireless", "ssid", Value::from(Vec::<u8>::new())),
+            ("connection", "id", Value::from("Cafe profile")),
+        ]);
+        assert_eq!(connection_ssid(&empty), Ssid::from("Cafe profile"));
+
+        assert_eq!(connection_ssid(&Settings::new()), Ssid::from("?"));
+    }
+}
@@ -0,0 +1,186 @@
+// Status bar output for waybar, i3blocks and polybar.
+
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::json;
+
+use crate::daemon;
+use crate::error::Error;
+use crate::hooks;
+use crate::theme;
+use crate::{
+    get_wifi_interfaces, parse_networks, pick_default_interface, run_command, run_status, signal_icon,
+    Network, SCAN_FIELDS,
+};
+
+const WATCH_INTERVAL: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BarFormat {
+    #[default]
+    Waybar,
+    I3blocks,
+    Polybar,
+}
+
+impl BarFormat {
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        match value {
+            "waybar" => Ok(BarFormat::Waybar),
+            "i3blocks" => Ok(BarFormat::I3blocks),
+            "polybar" => Ok(BarFormat::Polybar),
+            _ => Err(Error::Usage(format!(
+                "Unknown bar format '{}', expected waybar, i3blocks or polybar",
+                value
+            ))),
+        }
+    }
+}
+
+#[derive(PartialEq)]
+struct BarState {
+    interface: Option<String>,
+    state: String,
+    network: Option<Network>,
+}
+
+impl BarState {
+    fn unavailable(interface: Option<String>) -> Self {
+        Self {
+            interface,
+            state: "unavailable".to_string(),
+            network: None,
+        }
+    }
+}
+
+/// The network the device is associated with. Uses the cached scan results
+/// (`--rescan no`) so polling from a bar never triggers a scan.
+pub fn active_network(interface: &str) -> Result<Option<Network>, Error> {
+    // The daemon's list is already cached
+    if let Some(result) = daemon::call::<Vec<Network>>("list", json!({ "interface": interface })) {
+        return Ok(result?.into_iter().find(|n| n.in_use));
+    }
+    let output = run_command(
+        "nmcli",
+        &["-t", "-f", SCAN_FIELDS, "dev", "wifi", "list", "ifname", interface, "--rescan", "no"],
+    )?;
+    Ok(parse_networks(&output).into_iter().find(|n| n.in_use))
+}
+
+fn current_state(pinned: Option<&str>) -> Result<BarState, Error> {
+    // Re-detect every time so a dongle plugged in later shows up
+    let interface = match pinned {
+        Some(iface) => Some(iface.to_string()),
+        None => pick_default_interface(&get_wifi_interfaces()?),
+    };
+    let Some(iface) = interface else {
+        return Ok(BarState::unavailable(None));
+    };
+
+    let state = run_status(Some(&iface))?
+        .devices
+        .into_iter()
+        .next()
+        .map(|d| d.state)
+        .unwrap_or_else(|| "unavailable".to_string());
+    let network = if state == "connected" {
+        active_network(&iface)?
+    } else {
+        None
+    };
+    // Skip the in-between states so a roam doesn't look like a disconnect
+    if state == "connected" || state == "disconnected" {
+        hooks::observe(&iface, network.as_ref());
+    }
+    Ok(BarState {
+        interface: Some(iface),
+        state,
+        network,
+    })
+}
+
+/// CSS class / short state name: connected, connecting, disconnected or unavailable.
+fn state_class(state: &str) -> &'static str {
+    if state == "connected" {
+        "connected"
+    } else if state.starts_with("connecting") {
+        "connecting"
+    } else if state == "disconnected" {
+        "disconnected"
+    } else {
+        "unavailable"
+    }
+}
+
+fn render(format: BarFormat, bar: &BarState) -> String {
+    let class = state_class(&bar.state);
+    let (icon, label) = match &bar.network {
+        Some(net) => (signal_icon(net.signal), net.ssid.to_string()),
+        None => (theme::icons().disconnected, class.to_string()),
+    };
+    let text = format!("{} {}", icon, label);
+
+    match format {
+        BarFormat::Waybar => {
+            let mut tooltip = format!(
+                "{}: {}",
+                bar.interface.as_deref().unwrap_or("no Wi-Fi device"),
+                bar.state
+            );
+            if let Some(net) = &bar.network {
+                let security = if net.security.is_empty() { "open" } else { &net.security };
+                tooltip.push_str(&format!("\n{} ({}%, {})\n{}", net.ssid, net.signal, security, net.bssid));
+            }
+            json!({
+                "text": text,
+                "alt": class,
+                "class": class,
+                "tooltip": tooltip,
+                "percentage": bar.network.as_ref().map(|n| n.signal).unwrap_or(0),
+            })
+            .to_string()
+        }
+        // full_text, short_text, color
+        BarFormat::I3blocks => {
+            let color = match class {
+                "connected" => "#A3BE8C",
+                "connecting" => "#EBCB8B",
+                _ => "#BF616A",
+            };
+            format!("{}\n{}\n{}", text, icon, color)
+        }
+        BarFormat::Polybar => text,
+    }
+}
+
+/// Prints the bar line once, or with `watch` keeps printing a new line
+/// whenever the state changes.
+pub fn run(format: BarFormat, pinned: Option<&str>, watch: bool) -> Result<(), Error> {
+    let mut last: Option<BarState> = None;
+    loop {
+        let state = match current_state(pinned) {
+            Ok(state) => state,
+            Err(e) if !watch => return Err(e),
+            // e.g. NetworkManager restarting; keep watching
+            Err(_) => BarState::unavailable(pinned.map(String::from)),
+        };
+        if last.as_ref() != Some(&state) {
+            let mut stdout = io::stdout();
+            // The bar may close the pipe; there's nobody left to report that to
+            if writeln!(stdout, "{}", render(format, &state))
+                .and_then(|_| stdout.flush())
+                .is_err()
+            {
+                return Ok(());
+            }
+            last = Some(state);
+        }
+        if !watch {
+            return Ok(());
+        }
+        thread::sleep(WATCH_INTERVAL);
+    }
+}
@@ -0,0 +1,205 @@
+// Channel occupancy for the channel view.
+//
+// Every access point covers a range of frequencies: its primary channel plus,
+// at 40/80/160 MHz, the neighbouring channels of the block it is aligned to.
+// Channels are scored by the summed signal of the access points overlapping
+// them, and the lowest scores are suggested for a new access point. Where
+// nmcli doesn't report the channel width, 20 MHz is assumed.
+
+use crate::{Band, Network};
+
+pub const BANDS: [Band; 3] = [Band::Ghz2, Band::Ghz5, Band::Ghz6];
+
+/// Channels suggested at most.
+const RECOMMENDATIONS: usize = 3;
+
+/// Centre frequency of a 20 MHz channel, in MHz.
+pub fn channel_freq(band: Band, chan: u32) -> u32 {
+    match band {
+        Band::Ghz2 if chan == 14 => 2484,
+        Band::Ghz2 => 2407 + 5 * chan,
+        Band::Ghz5 => 5000 + 5 * chan,
+        Band::Ghz6 => 5950 + 5 * chan,
+        Band::Unknown => 0,
+    }
+}
+
+/// Frequency range shown for a band, in MHz.
+pub fn axis(band: Band) -> (f64, f64) {
+    match band {
+        Band::Ghz2 => (2400.0, 2496.0),
+        Band::Ghz5 => (5165.0, 5840.0),
+        Band::Ghz6 => (5940.0, 7130.0),
+        Band::Unknown => (0.0, 1.0),
+    }
+}
+
+/// Channels to label on the axis, as far as there's room.
+pub fn labels(band: Band) -> &'static [u32] {
+    match band {
+        Band::Ghz2 => &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14],
+        Band::Ghz5 => &[36, 44, 52, 60, 100, 108, 116, 124, 132, 140, 149, 157, 165],
+        Band::Ghz6 => &[1, 33, 65, 97, 129, 161, 193, 225],
+        Band::Unknown => &[],
+    }
+}
+
+/// Channels a new access point could use: the non-overlapping ones on
+/// 2.4 GHz, every 20 MHz channel on 5 GHz, and the preferred scanning
+/// channels on 6 GHz.
+fn candidates(band: Band) -> Vec<u32> {
+    match band {
+        Band::Ghz2 => vec![1, 6, 11],
+        Band::Ghz5 => (36..=64).step_by(4).chain((100..=144).step_by(4)).chain((149..=165).step_by(4)).collect(),
+        Band::Ghz6 => (5..=229).step_by(16).collect(),
+        Band::Unknown => Vec::new(),
+    }
+}
+
+/// 5 GHz channels that need radar detection before use.
+pub fn is_dfs(band: Band, chan: u32) -> bool {
+    band == Band::Ghz5 && (52..=144).contains(&chan)
+}
+
+/// Lowest and highest frequency `net` transmits on, in MHz.
+pub fn span(net: &Network) -> Option<(f64, f64)> {
+    let band = Band::of(net.freq);
+    if band == Band::Unknown || net.chan == 0 {
+        return None;
+    }
+    let freq = f64::from(net.freq);
+    let width = if net.bandwidth == 0 { 20 } else { net.bandwidth };
+    let span = match band {
+        // The secondary channel is usually above on the low channels
+        Band::Ghz2 if width >= 40 && net.chan <= 7 => (freq - 10.0, freq + 30.0),
+        Band::Ghz2 if width >= 40 => (freq - 30.0, freq + 10.0),
+        Band::Ghz2 => (freq - 10.0, freq + 10.0),
+        _ => {
+            // Wide channels are aligned blocks of 20 MHz channels, four
+            // channel numbers apart
+            let base = match band {
+                Band::Ghz5 if net.chan >= 149 => 149,
+                Band::Ghz5 => 36,
+                _ => 1,
+            };
+            let per_block = (width / 20).max(1);
+            let index = net.chan.saturating_sub(base) / 4;
+            let first = base + 4 * (index - index % per_block);
+            let low = f64::from(channel_freq(band, first)) - 10.0;
+            (low, low + f64::from(width))
+        }
+    };
+    Some(span)
+}
+
+pub struct Recommendation {
+    pub chan: u32,
+    /// Access points overlapping the channel
+    pub neighbours: usize,
+}
+
+/// The least crowded channels of `band`, best first.
+pub fn recommend(band: Band, access_points: &[Network]) -> Vec<Recommendation> {
+    let spans: Vec<((f64, f64), u8)> = access_points
+        .iter()
+        .filter(|ap| Band::of(ap.freq) == band)
+        .filter_map(|ap| span(ap).map(|span| (span, ap.signal)))
+        .collect();
+    let mut scored: Vec<(u32, u32, usize)> = candidates(band)
+        .into_iter()
+        .map(|chan| {
+            let centre = f64::from(channel_freq(band, chan));
+            let overlapping: Vec<u8> = spans
+                .iter()
+                .filter(|((low, high), _)| *low < centre + 10.0 && *high > centre - 10.0)
+                .map(|(_, signal)| *signal)
+                .collect();
+            let score = overlapping.iter().map(|&s| u32::from(s)).sum();
+            (chan, score, overlapping.len())
+        })
+        .collect();
+    // Radar detection can hold a DFS channel up for a minute, so those lose
+    // ties. The sort is stable, so after that the lower channel wins.
+    scored.sort_by_key(|&(chan, score, _)| (score, is_dfs(band, chan)));
+    scored
+        .into_iter()
+        .take(RECOMMENDATIONS)
+        .map(|(chan, _, neighbours)| Recommendation { chan, neighbours })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssid::Ssid;
+
+    /// An access point on `chan`, `bandwidth` MHz wide.
+    fn ap(band: Band, chan: u32, bandwidth: u32, signal: u8) -> Network {
+        Network {
+            ssid: Ssid::from("AP"),
+            bssid: String::new(),
+            security: String::new(),
+            signal,
+            in_use: false,
+            freq: channel_freq(band, chan),
+            chan,
+            bandwidth,
+            saved: false,
+            last_seen: None,
+            ap_path: String::new(),
+        }
+    }
+
+    fn chans(band: Band, access_points: &[Network]) -> Vec<u32> {
+        recommend(band, access_points).iter().map(|r| r.chan).collect()
+    }
+
+    #[test]
+    fn spans() {
+        let cases = [
+            // band, channel, width, low, high
+            (Band::Ghz2, 6, 20, 2427.0, 2447.0),
+            (Band::Ghz2, 6, 0, 2427.0, 2447.0),
+            (Band::Ghz2, 1, 40, 2402.0, 2442.0),
+            (Band::Ghz2, 11, 40, 2432.0, 2472.0),
+            (Band::Ghz5, 40, 20, 5190.0, 5210.0),
+            (Band::Ghz5, 36, 80, 5170.0, 5250.0),
+            (Band::Ghz5, 44, 80, 5170.0, 5250.0),
+            (Band::Ghz5, 52, 80, 5250.0, 5330.0),
+            (Band::Ghz5, 48, 40, 5210.0, 5250.0),
+            (Band::Ghz5, 100, 160, 5490.0, 5650.0),
+            (Band::Ghz5, 157, 40, 5775.0, 5815.0),
+            (Band::Ghz5, 149, 80, 5735.0, 5815.0),
+            (Band::Ghz6, 5, 160, 5945.0, 6105.0),
+            (Band::Ghz6, 37, 80, 6105.0, 6185.0),
+        ];
+        for (band, chan, width, low, high) in cases {
+            assert_eq!(span(&ap(band, chan, width, 50)), Some((low, high)), "ch {chan} @ {width} MHz");
+        }
+        assert_eq!(span(&ap(Band::Ghz5, 0, 20, 50)), None);
+        assert_eq!(span(&Network { freq: 900, ..ap(Band::Ghz2, 1, 20, 50) }), None);
+    }
+
+    #[test]
+    fn recommends_free_non_overlapping_channels() {
+        assert_eq!(chans(Band::Ghz2, &[ap(Band::Ghz2, 1, 20, 80), ap(Band::Ghz2, 6, 20, 60)])[0], 11);
+        // Quietest neighbours first when 1, 6 and 11 are all in use
+        let busy = [ap(Band::Ghz2, 1, 20, 80), ap(Band::Ghz2, 6, 20, 60), ap(Band::Ghz2, 11, 20, 40)];
+        assert_eq!(chans(Band::Ghz2, &busy), [11, 6, 1]);
+        // A 40 MHz network on 1 reaches into 6
+        let wide = recommend(Band::Ghz2, &[ap(Band::Ghz2, 1, 40, 50)]);
+        assert_eq!(wide.iter().map(|r| (r.chan, r.neighbours)).collect::<Vec<_>>(), [(11, 0), (1, 1), (6, 1)]);
+        // Other bands don't count
+        assert_eq!(chans(Band::Ghz2, &[ap(Band::Ghz5, 36, 80, 90)]), [1, 6, 11]);
+    }
+
+    #[test]
+    fn dfs_channels_lose_ties() {
+        assert_eq!(chans(Band::Ghz5, &[]), [36, 40, 44]);
+        // Both non-DFS blocks taken: 165 is the last free channel without radar detection
+        let taken = [ap(Band::Ghz5, 36, 80, 70), ap(Band::Ghz5, 149, 80, 70)];
+        assert_eq!(chans(Band::Ghz5, &taken), [165, 52, 56]);
+        assert!(is_dfs(Band::Ghz5, 52) && is_dfs(Band::Ghz5, 144));
+        assert!(!is_dfs(Band::Ghz5, 48) && !is_dfs(Band::Ghz5, 149) && !is_dfs(Band::Ghz6, 53));
+    }
+}
@@ -0,0 +1,496 @@
+// Configuration file.
+//
+// Read from `--config <path>`, or `$XDG_CONFIG_HOME/wifi_menu/config.toml`
+// (default `~/.config/wifi_menu/config.toml`) when it exists. Every key is
+// optional; `wifi_menu --print-default-config` prints a commented file with
+// the built-in values.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::{env, fs, io};
+
+use serde::Deserialize;
+
+use crate::error::Error;
+use crate::keymap::{KeysConfig, Keymap};
+use crate::SecurityFilter;
+
+pub const DEFAULT_CONFIG: &str = r#"# wifi_menu configuration
+
+# Only nmcli is supported for now
+backend = "nmcli"
+
+[ui]
+# Columns of the network list, in order: active, bars, ssid, signal, security
+columns = ["active", "bars", "ssid", "signal", "security"]
+# Preferred width of the SSID column. It grows to fit the longest SSID on wide
+# terminals; on narrow ones it shrinks, long SSIDs are cut off with "..." and
+# the signal, security, active and bars columns are dropped in that order.
+ssid_width = 25
+# "dark", "light", "high-contrast" or "monochrome"; when unset, NO_COLOR picks
+# monochrome and dark is used otherwise
+# theme = "dark"
+# "nerd" needs a Nerd Font; "unicode" and "ascii" work in any terminal
+icons = "nerd"
+# Signal percentages at which the bars icon steps up
+signal_thresholds = [20, 40, 60, 80]
+# Click, double-click and scroll in the TUI. While on, most terminals still
+# select text with Shift held down.
+mouse = true
+
+[behavior]
+# Re-read the network list every N seconds while browsing; 0 turns it off
+refresh_interval = 0
+# "signal", "ssid", "security", "band", "saved" or "last-seen"; the active
+# network always comes first
+sort = "signal"
+# Flip the order: weakest, Z-A, least secure, highest channel, unsaved or
+# longest unseen first
+sort_reverse = false
+# Ask before forgetting a network
+confirm_forget = true
+# Interface to start on instead of the connected (or first) one
+# default_interface = "wlan0"
+
+[survey]
+# Seconds between scans while recording with --survey
+interval = 10
+
+[dmenu]
+# Flag that puts the --dmenu launcher into password mode. When unset it is
+# guessed: -password for rofi, --password for fuzzel and wofi, "--password
+# indicator" for bemenu and -P (from the dmenu password patch) otherwise
+# password_flag = "-P"
+
+[history]
+# Remember every access point scanned, in $XDG_STATE_HOME/wifi_menu/history.json
+enabled = true
+# Forget access points that haven't been seen for this many days
+keep_days = 90
+
+# Per-interface defaults for the TUI and --list
+# [interfaces.wlan0]
+# min_signal = 20
+# security = "wpa2"
+
+# Key bindings per mode. Each entry replaces the keys of one action; keys are
+# written like "q", "Enter", "Esc", "Tab", "Up", "PgDn", "F5", "Ctrl-r" or "Alt-x".
+[keys.browse]
+next = ["Down", "j"]
+prev = ["Up", "k"]
+cycle_interface = ["i"]
+rescan = ["r"]
+connect = ["Enter"]
+quit = ["q", "Esc"]
+search = ["/"]
+next_match = ["n"]
+prev_match = ["N"]
+filter_open = ["o"]
+filter_saved = ["s"]
+filter_5ghz = ["5"]
+filter_signal = ["m"]
+clear_filter = ["x"]
+cycle_sort = ["S"]
+reverse_sort = ["R"]
+details = ["d"]
+channels = ["c"]
+waypoint = ["w"]
+history = ["H"]
+help = ["?"]
+
+[keys.password]
+submit = ["Enter"]
+toggle_password = ["Tab"]
+cancel = ["Esc"]
+
+[keys.action_menu]
+next = ["Down", "j"]
+prev = ["Up", "k"]
+select = ["Enter"]
+cancel = ["Esc"]
+help = ["?"]
+
+# Any other key answers no
+[keys.confirm]
+confirm = ["y", "Y"]
+
+[keys.help]
+next = ["Down", "j"]
+prev = ["Up", "k"]
+page_down = ["PgDn", "Space"]
+page_up = ["PgUp"]
+cancel = ["Esc", "q", "?"]
+
+# Typing edits the search; these keys don't
+[keys.search]
+submit = ["Enter"]
+next = ["Down"]
+prev = ["Up"]
+cancel = ["Esc"]
+
+[keys.channels]
+next = ["Right", "l", "Tab"]
+prev = ["Left", "h"]
+rescan = ["r"]
+cancel = ["Esc", "q", "c"]
+help = ["?"]
+
+# Naming a waypoint during a survey; typing edits the name
+[keys.waypoint]
+submit = ["Enter"]
+cancel = ["Esc"]
+
+[keys.history]
+next = ["Down", "j"]
+prev = ["Up", "k"]
+page_down = ["PgDn", "Space"]
+page_up = ["PgUp"]
+cancel = ["Esc", "q", "H"]
+help = ["?"]
+"#;
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub backend: Backend,
+    pub ui: UiConfig,
+    pub behavior: BehaviorConfig,
+    pub survey: SurveyConfig,
+    pub dmenu: DmenuConfig,
+    pub history: HistoryConfig,
+    pub interfaces: BTreeMap<String, InterfaceConfig>,
+    pub keys: KeysConfig,
+    /// Built from `keys` when the file is loaded
+    #[serde(skip)]
+    pub keymap: Keymap,
+    /// File the config was read from; `None` for the built-in defaults
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Nmcli,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Nmcli => "nmcli",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiConfig {
+    pub columns: Vec<Column>,
+    pub ssid_width: usize,
+    pub theme: Option<Theme>,
+    pub icons: IconSet,
+    pub signal_thresholds: [u8; 4],
+    pub mouse: bool,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            columns: vec![Column::Active, Column::Bars, Column::Ssid, Column::Signal, Column::Security],
+            ssid_width: 25,
+            theme: None,
+            icons: IconSet::default(),
+            signal_thresholds: [20, 40, 60, 80],
+            mouse: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    /// Marker for the network in use
+    Active,
+    /// Signal strength icon
+    Bars,
+    Ssid,
+    /// Signal strength in percent
+    Signal,
+    /// Lock icon for secured networks
+    Security,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+    Monochrome,
+}
+
+impl Theme {
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        match value {
+            "dark" => Ok(Theme::Dark),
+            "light" => Ok(Theme::Light),
+            "high-contrast" => Ok(Theme::HighContrast),
+            "monochrome" => Ok(Theme::Monochrome),
+            _ => Err(Error::Usage(format!(
+                "Unknown theme '{}', expected dark, light, high-contrast or monochrome",
+                value
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IconSet {
+    #[default]
+    Nerd,
+    Unicode,
+    Ascii,
+}
+
+impl IconSet {
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        match value {
+            "nerd" => Ok(IconSet::Nerd),
+            "unicode" => Ok(IconSet::Unicode),
+            "ascii" => Ok(IconSet::Ascii),
+            _ => Err(Error::Usage(format!("Unknown icon set '{}', expected nerd, unicode or ascii", value))),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BehaviorConfig {
+    pub refresh_interval: u64,
+    pub sort: SortKey,
+    pub sort_reverse: bool,
+    pub confirm_forget: bool,
+    pub default_interface: Option<String>,
+}
+
+impl Default for BehaviorConfig {
+    fn default() -> Self {
+        Self {
+            refresh_interval: 0,
+            sort: SortKey::default(),
+            sort_reverse: false,
+            confirm_forget: true,
+            default_interface: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SurveyConfig {
+    pub interval: u64,
+}
+
+impl Default for SurveyConfig {
+    fn default() -> Self {
+        Self { interval: 10 }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DmenuConfig {
+    pub password_flag: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    pub enabled: bool,
+    pub keep_days: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            keep_days: 90,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortKey {
+    /// Strongest first
+    #[default]
+    Signal,
+    /// A-Z, ignoring case
+    Ssid,
+    /// Most secure first
+    Security,
+    /// 2.4 GHz before 5 GHz before 6 GHz, then by channel
+    Band,
+    /// Networks with a saved profile first
+    Saved,
+    /// Most recently seen by NetworkManager first
+    LastSeen,
+}
+
+impl SortKey {
+    const ALL: [SortKey; 6] =
+        [SortKey::Signal, SortKey::Ssid, SortKey::Security, SortKey::Band, SortKey::Saved, SortKey::LastSeen];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::Signal => "signal",
+            SortKey::Ssid => "ssid",
+            SortKey::Security => "security",
+            SortKey::Band => "band",
+            SortKey::Saved => "saved",
+            SortKey::LastSeen => "last-seen",
+        }
+    }
+
+    /// The key after this one, wrapping around.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&key| key == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct InterfaceConfig {
+    pub min_signal: u8,
+    pub security: Option<SecurityFilter>,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// `$XDG_CONFIG_HOME/wifi_menu`, falling back to `~/.config/wifi_menu`.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("wifi_menu"))
+}
+
+/// `$XDG_STATE_HOME/wifi_menu`, falling back to `~/.local/state/wifi_menu`.
+pub fn state_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))?;
+    Some(base.join("wifi_menu"))
+}
+
+/// Loads the config once at startup. An explicit `path` must exist; the
+/// default location is optional.
+pub fn load(path: Option<&str>) -> Result<(), Error> {
+    let (path, required) = match path {
+        Some(path) => (PathBuf::from(path), true),
+        None => match config_dir() {
+            Some(dir) => (dir.join("config.toml"), false),
+            None => return Ok(()),
+        },
+    };
+
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !required => return Ok(()),
+        Err(source) => {
+            return Err(Error::Io {
+                context: format!("Cannot read {}", path.display()),
+                source,
+            })
+        }
+    };
+    let mut config: Config = toml::from_str(&text).map_err(|e| Error::Config {
+        path: path.display().to_string(),
+        message: e.to_string().trim_end().to_string(),
+    })?;
+
+    config.keymap = Keymap::new(&config.keys).map_err(|message| Error::Config {
+        path: path.display().to_string(),
+        message,
+    })?;
+    config.validate().map_err(|message| Error::Config {
+        path: path.display().to_string(),
+        message,
+    })?;
+
+    config.path = Some(path);
+    let _ = CONFIG.set(config);
+    Ok(())
+}
+
+/// The loaded config, or the built-in defaults.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+impl Config {
+    /// Checks the values serde can't: ranges, ordering and empty lists.
+    fn validate(&self) -> Result<(), String> {
+        if self.ui.columns.is_empty() {
+            return Err("ui.columns must list at least one column".to_string());
+        }
+        if self.ui.ssid_width == 0 {
+            return Err("ui.ssid_width must be at least 1".to_string());
+        }
+        if !self.ui.signal_thresholds.is_sorted() {
+            return Err("ui.signal_thresholds must be in ascending order".to_string());
+        }
+        if self.ui.signal_thresholds.iter().any(|&step| step > 100) {
+            return Err("ui.signal_thresholds must be percentages from 0 to 100".to_string());
+        }
+        if self.survey.interval == 0 {
+            return Err("survey.interval must be at least 1 second".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn interface(&self, name: &str) -> Option<&InterfaceConfig> {
+        self.interfaces.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keymap::KeysConfig;
+
+    #[test]
+    fn default_config_matches_the_defaults() {
+        let mut parsed: Config = toml::from_str(DEFAULT_CONFIG).unwrap();
+        // The file spells out every binding the built-in keymap has
+        parsed.keymap = Keymap::new(&parsed.keys).unwrap();
+        parsed.keys = KeysConfig::new();
+        assert_eq!(parsed, Config::default());
+        assert_eq!(parsed.validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_values_that_leave_nothing_to_show() {
+        let invalid = [
+            ("[ui]\ncolumns = []", "ui.columns"),
+            ("[ui]\nssid_width = 0", "ui.ssid_width"),
+            ("[ui]\nsignal_thresholds = [20, 60, 40, 80]", "ui.signal_thresholds"),
+            ("[ui]\nsignal_thresholds = [20, 40, 60, 101]", "ui.signal_thresholds"),
+            ("[survey]\ninterval = 0", "survey.interval"),
+        ];
+        for (text, key) in invalid {
+            let config: Config = toml::from_str(text).unwrap();
+            let message = config.validate().unwrap_err();
+            assert!(message.starts_with(key), "{text}: {message}");
+        }
+        let config: Config = toml::from_str("[ui]\ncolumns = [\"ssid\"]\nssid_width = 1").unwrap();
+        assert_eq!(config.validate(), Ok(()));
+    }
+}
@@ -0,0 +1,428 @@
+// Background daemon and its client.
+//
+// `wifi_menu --daemon` keeps the Wi-Fi devices, their status and the scan
+// results cached and serves them on a UNIX socket. Each request and response
+// is one line of JSON-RPC 2.0; the methods are documented in the README.
+// Every other invocation checks for the socket on first use and, if a daemon
+// answers, sends its nmcli work there instead of running nmcli itself.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::{env, fs, thread};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::error::Error;
+use crate::hooks;
+use crate::output::{DeviceStatus, StatusReport};
+use crate::ssid::Ssid;
+use crate::{
+    connect_network, delete_connection, disconnect_interface, get_wifi_interfaces, rescan_interface,
+    run_status, scan_interface, scan_networks, Network,
+};
+
+const SOCKET_NAME: &str = "wifi_menu.sock";
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+/// A client that doesn't take a line within this long is dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a client waits for an answer. A connect can take nmcli's whole
+/// 90 s activation timeout.
+const READ_TIMEOUT: Duration = Duration::from_secs(120);
+/// Notifications a subscriber may fall behind by before it is dropped.
+const SUBSCRIBER_BACKLOG: usize = 16;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// Application error; `data` holds the same object `--json` prints under "error"
+const SERVER_ERROR: i64 = -32000;
+
+/// `$XDG_RUNTIME_DIR/wifi_menu.sock`, or `/tmp/wifi_menu-<uid>/wifi_menu.sock`
+/// without one.
+pub fn socket_path() -> PathBuf {
+    let dir = match env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(format!("/tmp/wifi_menu-{}", current_uid().unwrap_or_default())),
+    };
+    dir.join(SOCKET_NAME)
+}
+
+fn current_uid() -> Option<u32> {
+    fs::metadata("/proc/self").map(|m| m.uid()).ok()
+}
+
+/// Requests carry passwords, so the socket has to be in a directory nobody
+/// else can get into, like the 0700 `XDG_RUNTIME_DIR`. Otherwise another
+/// user could have put their own socket there first.
+fn check_private(dir: &Path) -> Result<(), String> {
+    let uid = current_uid().ok_or("cannot tell the current user")?;
+    let meta = fs::symlink_metadata(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    if !meta.is_dir() || meta.uid() != uid || meta.mode() & 0o077 != 0 {
+        return Err(format!("{} is not a directory private to the current user", dir.display()));
+    }
+    Ok(())
+}
+
+// --- Client ---
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+static CONNECTION: Mutex<Option<BufReader<UnixStream>>> = Mutex::new(None);
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Stops this process from talking to a daemon (`--no-daemon`, and the daemon itself).
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Takes the idle connection to the daemon, or makes a new one if there is
+/// none (first use, the daemon went away, or another request is using it).
+fn take_connection() -> Option<BufReader<UnixStream>> {
+    if DISABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+    let idle = CONNECTION.lock().unwrap_or_else(|e| e.into_inner()).take();
+    idle.or_else(connect)
+}
+
+/// Keeps a connection that answered for the next request.
+fn put_back(reader: BufReader<UnixStream>) {
+    let mut idle = CONNECTION.lock().unwrap_or_else(|e| e.into_inner());
+    if idle.is_none() {
+        *idle = Some(reader);
+    }
+}
+
+/// Connects to the daemon's socket if it is where only we could have put it.
+fn connect() -> Option<BufReader<UnixStream>> {
+    let path = socket_path();
+    check_private(path.parent()?).ok()?;
+    let meta = fs::symlink_metadata(&path).ok()?;
+    if Some(meta.uid()) != current_uid() {
+        return None;
+    }
+    let stream = UnixStream::connect(path).ok()?;
+    stream.set_read_timeout(Some(READ_TIMEOUT)).ok()?;
+    Some(BufReader::new(stream))
+}
+
+/// Whether this process hands its work to a running daemon.
+pub fn in_use() -> bool {
+    take_connection().map(put_back).is_some()
+}
+
+/// Sends a request to the daemon. Returns `None` when no daemon is running,
+/// in which case the caller does the work itself.
+pub fn call<T: DeserializeOwned>(method: &str, params: Value) -> Option<Result<T, Error>> {
+    let mut reader = take_connection()?;
+
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+    if writeln!(reader.get_mut(), "{}", request).is_err() {
+        // The daemon went away before it got the request, so do the work
+        // here. The next call connects again in case it was restarted.
+        return None;
+    }
+    // From here on the daemon may already be acting on the request; doing it
+    // again here could connect or forget twice
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(n) if n > 0 => put_back(reader),
+        Ok(_) => return Some(Err(Error::Daemon(format!("connection closed during '{}'", method)))),
+        Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+            let message = format!("no answer to '{}' within {} s", method, READ_TIMEOUT.as_secs());
+            return Some(Err(Error::Daemon(message)));
+        }
+        Err(e) => return Some(Err(Error::Daemon(format!("cannot read the answer: {}", e)))),
+    }
+
+    let response: Value = match serde_json::from_str(&line) {
+        Ok(value) => value,
+        Err(e) => return Some(Err(Error::Daemon(format!("invalid response: {}", e)))),
+    };
+    if let Some(error) = response.get("error") {
+        return Some(Err(match error.get("data") {
+            Some(data) => Error::from_json(data),
+            None => Error::Daemon(error["message"].as_str().unwrap_or_default().to_string()),
+        }));
+    }
+    Some(
+        serde_json::from_value(response["result"].clone())
+            .map_err(|e| Error::Daemon(format!("invalid response: {}", e))),
+    )
+}
+
+// --- Server ---
+
+#[derive(Clone, Default, PartialEq, Serialize)]
+struct Snapshot {
+    interfaces: Vec<String>,
+    status: StatusReport,
+    networks: BTreeMap<String, Vec<Network>>,
+}
+
+#[derive(Default)]
+struct Shared {
+    snapshot: Mutex<Snapshot>,
+    /// Every BSSID of the latest scans. Not part of the snapshot, since
+    /// subscribers only see the network lists.
+    access_points: Mutex<BTreeMap<String, Vec<Network>>>,
+    /// One queue per subscriber, emptied by a thread of its own
+    subscribers: Mutex<Vec<SyncSender<String>>>,
+    // Keeps the poller and post-action refreshes from racing each other
+    refreshing: Mutex<()>,
+}
+
+impl Shared {
+    fn snapshot(&self) -> Snapshot {
+        self.snapshot.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Re-reads everything from nmcli and notifies subscribers if it changed.
+    fn refresh(&self) -> Result<(), Error> {
+        let _guard = self.refreshing.lock().unwrap_or_else(|e| e.into_inner());
+        let interfaces = get_wifi_interfaces()?;
+        let status = run_status(None)?;
+        let mut networks = BTreeMap::new();
+        let mut access_points = BTreeMap::new();
+        for iface in &interfaces {
+            match scan_interface(iface) {
+                Ok(scan) => {
+                    hooks::observe(iface, scan.networks.iter().find(|n| n.in_use));
+                    networks.insert(iface.clone(), scan.networks);
+                    access_points.insert(iface.clone(), scan.access_points);
+                }
+                // A failed scan says nothing about the link, so don't fire hooks for it
+                Err(_) => {
+                    networks.insert(iface.clone(), Vec::new());
+                    access_points.insert(iface.clone(), Vec::new());
+                }
+            }
+        }
+        *self.access_points.lock().unwrap_or_else(|e| e.into_inner()) = access_points;
+        let next = Snapshot { interfaces, status, networks };
+
+        let mut snapshot = self.snapshot.lock().unwrap_or_else(|e| e.into_inner());
+        if *snapshot == next {
+            return Ok(());
+        }
+        *snapshot = next;
+        let notification = json!({ "jsonrpc": "2.0", "method": "changed", "params": *snapshot });
+        drop(snapshot);
+
+        // Never waits on a client: subscribers that went away or fell too
+        // far behind are dropped instead
+        let line = format!("{}\n", notification);
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|queue| queue.try_send(line.clone()).is_ok());
+        Ok(())
+    }
+}
+
+/// Runs the daemon until it is killed.
+pub fn serve() -> Result<(), Error> {
+    disable();
+    let path = socket_path();
+    let dir = path.parent().unwrap_or(Path::new("/"));
+    // Only the /tmp fallback is ours to create; XDG_RUNTIME_DIR comes from the login
+    if env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()).is_none() {
+        match fs::DirBuilder::new().mode(0o700).create(dir) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(source) => {
+                return Err(Error::Io {
+                    context: format!("Cannot create {}", dir.display()),
+                    source,
+                })
+            }
+        }
+    }
+    check_private(dir).map_err(Error::Daemon)?;
+    if UnixStream::connect(&path).is_ok() {
+        return Err(Error::Daemon(format!("already running on {}", path.display())));
+    }
+    // Left behind by a daemon that didn't shut down cleanly
+    let _ = fs::remove_file(&path);
+
+    let listener = UnixListener::bind(&path).map_err(|source| Error::Io {
+        context: format!("Cannot listen on {}", path.display()),
+        source,
+    })?;
+    // Requests can carry passwords, so only the owner may connect
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).map_err(|source| Error::Io {
+        context: format!("Cannot restrict {}", path.display()),
+        source,
+    })?;
+
+    let shared = Arc::new(Shared::default());
+    shared.refresh()?;
+    eprintln!("wifi_menu daemon listening on {}", path.display());
+
+    let poller = Arc::clone(&shared);
+    thread::spawn(move || loop {
+        thread::sleep(REFRESH_INTERVAL);
+        // NetworkManager restarting shouldn't take the daemon down with it
+        let _ = poller.refresh();
+    });
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let shared = Arc::clone(&shared);
+        thread::spawn(move || handle_client(stream, &shared));
+    }
+    Ok(())
+}
+
+/// The sending half of a client connection. Responses and notifications are
+/// written under its lock, so lines never interleave.
+type Writer = Arc<Mutex<UnixStream>>;
+
+fn send(writer: &Writer, line: &str) -> io::Result<()> {
+    let mut stream = writer.lock().unwrap_or_else(|e| e.into_inner());
+    let sent = stream.write_all(line.as_bytes());
+    if sent.is_err() {
+        // A line cut short by the timeout leaves the stream unusable
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+    sent
+}
+
+fn handle_client(stream: UnixStream, shared: &Shared) {
+    let Ok(writer) = stream.try_clone() else { return };
+    if writer.set_write_timeout(Some(WRITE_TIMEOUT)).is_err() {
+        return;
+    }
+    let writer: Writer = Arc::new(Mutex::new(writer));
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else { return };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => {
+                let id = request.get("id").cloned().unwrap_or(Value::Null);
+                let method = request["method"].as_str().unwrap_or_default();
+                let result = if method == "subscribe" {
+                    subscribe(&writer, shared)
+                } else {
+                    dispatch(method, &request["params"], shared)
+                };
+                match result {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err((code, message, data)) => rpc_error(id, code, message, data),
+                }
+            }
+            Err(e) => rpc_error(Value::Null, PARSE_ERROR, e.to_string(), None),
+        };
+        if send(&writer, &format!("{}\n", response)).is_err() {
+            return;
+        }
+    }
+}
+
+type RpcError = (i64, String, Option<Value>);
+
+fn rpc_error(id: Value, code: i64, message: String, data: Option<Value>) -> Value {
+    let mut error = json!({ "code": code, "message": message });
+    if let Some(data) = data {
+        error["data"] = data;
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
+
+fn subscribe(writer: &Writer, shared: &Shared) -> Result<Value, RpcError> {
+    let (queue, notifications) = mpsc::sync_channel::<String>(SUBSCRIBER_BACKLOG);
+    let writer = Arc::clone(writer);
+    // Ends when a write fails, which also disconnects the queue
+    thread::spawn(move || {
+        for line in notifications {
+            if send(&writer, &line).is_err() {
+                return;
+            }
+        }
+    });
+    shared.subscribers.lock().unwrap_or_else(|e| e.into_inner()).push(queue);
+    Ok(json!(shared.snapshot()))
+}
+
+fn dispatch(method: &str, params: &Value, shared: &Shared) -> Result<Value, RpcError> {
+    let param = |key: &str| -> Result<&str, RpcError> {
+        params[key]
+            .as_str()
+            .ok_or_else(|| (INVALID_PARAMS, format!("missing string parameter '{}'", key), None))
+    };
+    let optional = |key: &str| params[key].as_str().unwrap_or_default();
+    let ssid = || {
+        let text = param("ssid")?;
+        Ssid::parse_escaped(text).ok_or_else(|| (INVALID_PARAMS, format!("invalid SSID '{}'", text), None))
+    };
+
+    let result = match method {
+        "interfaces" => Ok(json!(shared.snapshot().interfaces)),
+        "status" => Ok(json!(cached_status(&shared.snapshot(), params["interface"].as_str()))),
+        "list" => {
+            let iface = param("interface")?;
+            match shared.snapshot().networks.get(iface) {
+                Some(networks) => Ok(json!(networks)),
+                // Not a device we know about; let nmcli produce the error
+                None => scan_networks(iface).map(|n| json!(n)),
+            }
+        }
+        "access_points" => {
+            let iface = param("interface")?;
+            let cached = shared.access_points.lock().unwrap_or_else(|e| e.into_inner()).get(iface).cloned();
+            match cached {
+                Some(access_points) => Ok(json!(access_points)),
+                None => scan_interface(iface).map(|scan| json!(scan.access_points)),
+            }
+        }
+        "connect" => connect_network(
+            &ssid()?,
+            optional("bssid"),
+            optional("password"),
+            optional("security"),
+            param("interface")?,
+        )
+        .map(|out| json!(out)),
+        "disconnect" => disconnect_interface(param("interface")?).map(|out| json!(out)),
+        "forget" => delete_connection(&ssid()?).map(|out| json!(out)),
+        "rescan" => rescan_interface(param("interface")?).map(|out| json!(out)),
+        _ => return Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method), None)),
+    };
+
+    // Actions change state, so clients shouldn't have to wait for the next poll
+    if matches!(method, "connect" | "disconnect" | "forget" | "rescan") {
+        let _ = shared.refresh();
+    }
+    result.map_err(|e| (SERVER_ERROR, e.to_string(), Some(e.to_json())))
+}
+
+/// Same shape as `run_status`, answered from the cache.
+fn cached_status(snapshot: &Snapshot, interface: Option<&str>) -> StatusReport {
+    let Some(iface) = interface else {
+        return snapshot.status.clone();
+    };
+    let mut devices: Vec<DeviceStatus> =
+        snapshot.status.devices.iter().filter(|d| d.interface == iface).cloned().collect();
+    if devices.is_empty() {
+        devices.push(DeviceStatus {
+            interface: iface.to_string(),
+            state: "unavailable".to_string(),
+            connection: None,
+        });
+    }
+    StatusReport { devices }
+}
+
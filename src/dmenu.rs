@@ -0,0 +1,326 @@
+// dmenu-style frontend.
+//
+// Drives the same scan/connect/forget logic as the TUI, but shows every menu
+// through an external launcher (dmenu, rofi, fuzzel, wofi, bemenu) that reads
+// choices on stdin and prints the selection on stdout.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::error::{ConnectError, Error};
+use crate::output::ActionReport;
+use crate::{
+    active_marker, config, connect_network, delete_connection, disconnect_interface, lock_icon,
+    needs_password, rescan_interface, scan_networks, signal_icon, sort_networks, theme,
+    validate_password, Network,
+};
+
+/// Password prompts, including ones rejected before connecting, before giving up
+const MAX_PASSWORD_ATTEMPTS: usize = 3;
+
+pub struct Menu {
+    command: String,
+    password_flag: String,
+}
+
+impl Menu {
+    /// `password_flag` (from `--dmenu-password-flag` or `dmenu.password_flag`)
+    /// is what hides input; without it, it is guessed from the launcher's name.
+    pub fn new(command: &str, password_flag: Option<&str>) -> Self {
+        let program = command
+            .split_whitespace()
+            .next()
+            .map(|p| Path::new(p).file_name().and_then(|n| n.to_str()).unwrap_or(p))
+            .unwrap_or("");
+        let guessed = match program {
+            "rofi" => "-password",
+            "fuzzel" | "wofi" => "--password",
+            // bemenu's password mode takes how to show the typed text
+            "bemenu" => "--password indicator",
+            // Upstream dmenu has no password mode; -P comes from the common password patch
+            _ => "-P",
+        };
+        Self {
+            command: command.to_string(),
+            password_flag: password_flag.unwrap_or(guessed).to_string(),
+        }
+    }
+
+    /// Shows `entries` and returns the chosen line, or `None` if the user dismissed the menu.
+    fn choose(&self, prompt: &str, entries: &[String], password: bool) -> Result<Option<String>, Error> {
+        // The prompt is passed as $1 so it never has to be quoted into the command
+        let mut script = format!("{} -p \"$1\"", self.command);
+        if password {
+            script.push(' ');
+            script.push_str(&self.password_flag);
+        }
+
+        let mut child = Command::new("sh")
+            .args(["-c", &script, "sh", prompt])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|source| Error::Spawn {
+                command: self.command.clone(),
+                source,
+            })?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            // A launcher that exits early closes the pipe; that's not our error
+            let _ = stdin.write_all(entries.join("\n").as_bytes());
+        }
+        let output = child.wait_with_output().map_err(|source| Error::Io {
+            context: format!("Cannot read selection from '{}'", self.command),
+            source,
+        })?;
+
+        let selection = String::from_utf8_lossy(&output.stdout)
+            .trim_end_matches(['\n', '\r'])
+            .to_string();
+        if !output.status.success() || selection.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(selection))
+    }
+}
+
+fn rescan_entry() -> String {
+    format!("{} Rescan", theme::icons().rescan)
+}
+
+fn entry_for(net: &Network) -> String {
+    format!(
+        "{}{} {} {:>3}% {}",
+        active_marker(net.in_use),
+        signal_icon(net.signal),
+        net.ssid,
+        net.signal,
+        lock_icon(&net.security)
+    )
+}
+
+/// One line per network, then the rescan entry.
+fn entries(networks: &[Network]) -> Vec<String> {
+    let mut entries: Vec<String> = networks.iter().map(entry_for).collect();
+    entries.push(rescan_entry());
+    entries
+}
+
+#[derive(Debug, PartialEq)]
+enum Selection<'a> {
+    Rescan,
+    Network(&'a Network),
+    /// Launchers like rofi can return free text that matches no entry
+    Other,
+}
+
+fn selection<'a>(networks: &'a [Network], choice: &str) -> Selection<'a> {
+    if choice == rescan_entry() {
+        return Selection::Rescan;
+    }
+    match networks.iter().find(|net| entry_for(net) == choice) {
+        Some(net) => Selection::Network(net),
+        None => Selection::Other,
+    }
+}
+
+/// Runs one round of the menu: pick a network, then connect to it or show the
+/// action menu if it's the active one. Returns `None` if the user backed out.
+pub fn run(menu: &Menu, interface: &str) -> Result<Option<ActionReport>, Error> {
+    loop {
+        let mut networks = scan_networks(interface)?;
+        let behavior = &config::get().behavior;
+        sort_networks(&mut networks, behavior.sort, behavior.sort_reverse);
+        let prompt = format!("Wi-Fi ({})", interface);
+        let Some(choice) = menu.choose(&prompt, &entries(&networks), false)? else {
+            return Ok(None);
+        };
+        let net = match selection(&networks, &choice) {
+            Selection::Rescan => {
+                rescan_interface(interface)?;
+                continue;
+            }
+            Selection::Network(net) => net,
+            Selection::Other => return Ok(None),
+        };
+
+        let mut report = ActionReport {
+            interface: Some(interface.to_string()),
+            ssid: Some(net.ssid.to_string()),
+            ok: true,
+            ..Default::default()
+        };
+
+        if net.in_use {
+            let actions: Vec<String> = ["Disconnect", "Forget", "Cancel"].map(String::from).to_vec();
+            let out = match menu.choose(&net.ssid.to_string(), &actions, false)?.as_deref() {
+                Some("Disconnect") => {
+                    report.action = "disconnect";
+                    disconnect_interface(interface)?
+                }
+                Some("Forget") => {
+                    report.action = "forget";
+                    delete_connection(&net.ssid)?
+                }
+                _ => return Ok(None),
+            };
+            report.message = Some(out).filter(|o| !o.is_empty());
+            return Ok(Some(report));
+        }
+
+        report.action = "connect";
+        let connect = |password: &str| connect_network(&net.ssid, &net.bssid, password, &net.security, interface);
+        let Some(out) = connect_with_password(menu, net, connect)? else {
+            return Ok(None);
+        };
+        report.message = Some(out).filter(|o| !o.is_empty());
+        return Ok(Some(report));
+    }
+}
+
+/// Tries the saved profile or open network first, then asks for a password
+/// until one works. Returns `None` if the user dismissed the prompt.
+fn connect_with_password(
+    menu: &Menu,
+    net: &Network,
+    mut connect: impl FnMut(&str) -> Result<String, Error>,
+) -> Result<Option<String>, Error> {
+    let mut result = connect("");
+    let mut prompt = format!("Password for {}", net.ssid);
+    let mut attempts = 0;
+    while let Err(Error::Connect { kind: ConnectError::BadSecrets, .. }) = &result {
+        if !needs_password(&net.security) || attempts == MAX_PASSWORD_ATTEMPTS {
+            break;
+        }
+        attempts += 1;
+        let Some(password) = menu.choose(&prompt, &[], true)? else {
+            return Ok(None);
+        };
+        if let Err(e) = validate_password(&net.security, &password) {
+            if attempts == MAX_PASSWORD_ATTEMPTS {
+                return Err(Error::Usage(e));
+            }
+            prompt = e;
+            continue;
+        }
+        result = connect(&password);
+        prompt = format!("Wrong password for {}", net.ssid);
+    }
+    result.map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_networks;
+    use std::fs;
+    use std::path::PathBuf;
+
+    const SCAN: &str = "\
+*:Home Net:AA\\:BB\\:CC\\:DD\\:EE\\:01:WPA2:82:2437 MHz:6:/ap/1:486F6D65204E6574
+ :Open Cafe:AA\\:BB\\:CC\\:DD\\:EE\\:02::40:2412 MHz:1:/ap/2:4F70656E2043616665
+";
+
+    /// A launcher that records its arguments and input, prints `reply` and
+    /// exits with `status`.
+    fn fake_menu(name: &str, reply: &str, status: i32) -> (Menu, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("wifi_menu-dmenu-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("menu.sh");
+        let body = format!(
+            "printf '%s\\n' \"$@\" > '{dir}/args'\necho >> '{dir}/calls'\ncat > '{dir}/stdin'\nprintf '%s' '{reply}'\nexit {status}\n",
+            dir = dir.display(),
+        );
+        fs::write(&script, body).unwrap();
+        (Menu::new(&format!("sh {}", script.display()), None), dir)
+    }
+
+    fn read(dir: &Path, name: &str) -> String {
+        fs::read_to_string(dir.join(name)).unwrap()
+    }
+
+    #[test]
+    fn writes_entries_and_picks_the_network() {
+        let networks = parse_networks(SCAN);
+        let entries = entries(&networks);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[2], rescan_entry());
+
+        let (menu, dir) = fake_menu("pick", &format!("{}\n", entries[1]), 0);
+        let choice = menu.choose("Wi-Fi (wlan0)", &entries, false).unwrap();
+        assert_eq!(read(&dir, "stdin"), entries.join("\n"));
+        assert_eq!(read(&dir, "args"), "-p\nWi-Fi (wlan0)\n");
+        assert_eq!(choice.as_deref(), Some(entries[1].as_str()));
+        assert_eq!(selection(&networks, &choice.unwrap()), Selection::Network(&networks[1]));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn recognises_rescan_and_free_text() {
+        let networks = parse_networks(SCAN);
+        assert_eq!(selection(&networks, &rescan_entry()), Selection::Rescan);
+        assert_eq!(selection(&networks, "Home Net"), Selection::Other);
+    }
+
+    #[test]
+    fn password_prompt_passes_the_flag_and_the_prompt_verbatim() {
+        let (menu, dir) = fake_menu("password", "hunter22", 0);
+        let prompt = "Password for \"Cafe\" $HOME";
+        let password = menu.choose(prompt, &[], true).unwrap();
+        assert_eq!(password.as_deref(), Some("hunter22"));
+        assert_eq!(read(&dir, "args"), format!("-p\n{}\n-P\n", prompt));
+        assert_eq!(read(&dir, "stdin"), "");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn password_flag_is_guessed_unless_given() {
+        assert_eq!(Menu::new("rofi -dmenu -i", None).password_flag, "-password");
+        assert_eq!(Menu::new("/usr/bin/fuzzel --dmenu", None).password_flag, "--password");
+        assert_eq!(Menu::new("bemenu -l 10", None).password_flag, "--password indicator");
+        assert_eq!(Menu::new("dmenu -l 15", None).password_flag, "-P");
+        assert_eq!(Menu::new("bemenu", Some("-x none")).password_flag, "-x none");
+    }
+
+    #[test]
+    fn gives_up_after_repeated_invalid_passwords() {
+        let networks = parse_networks(SCAN);
+        let (menu, dir) = fake_menu("invalid", "short", 0);
+        let mut connects = 0;
+        let result = connect_with_password(&menu, &networks[0], |_| {
+            connects += 1;
+            Err(Error::Connect { kind: ConnectError::BadSecrets, failure: None })
+        });
+        assert!(matches!(result, Err(Error::Usage(_))));
+        assert_eq!(connects, 1);
+        assert_eq!(read(&dir, "calls").lines().count(), MAX_PASSWORD_ATTEMPTS);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn gives_up_after_repeated_wrong_passwords() {
+        let networks = parse_networks(SCAN);
+        let (menu, dir) = fake_menu("wrong", "hunter22", 0);
+        let mut connects = 0;
+        let result = connect_with_password(&menu, &networks[0], |_| {
+            connects += 1;
+            Err(Error::Connect { kind: ConnectError::BadSecrets, failure: None })
+        });
+        assert!(matches!(result, Err(Error::Connect { kind: ConnectError::BadSecrets, .. })));
+        assert_eq!(connects, 1 + MAX_PASSWORD_ATTEMPTS);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn dismissing_the_menu_selects_nothing() {
+        let entries = vec!["a".to_string(), "b".to_string()];
+        let (menu, dir) = fake_menu("cancel", "a", 1);
+        assert_eq!(menu.choose("Wi-Fi", &entries, false).unwrap(), None);
+        fs::remove_dir_all(dir).unwrap();
+
+        let (menu, dir) = fake_menu("empty", "", 0);
+        assert_eq!(menu.choose("Wi-Fi", &entries, false).unwrap(), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
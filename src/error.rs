@@ -0,0 +1,379 @@
+use std::{fmt, io};
+
+use serde_json::{json, Value};
+
+/// Crate-wide error type. Command failures keep enough context to tell what
+/// was run and why it failed, with secrets already redacted from the args.
+#[derive(Debug)]
+pub enum Error {
+    /// The command could not be started (e.g. nmcli is not installed).
+    Spawn { command: String, source: io::Error },
+    /// The command ran and exited unsuccessfully.
+    Command(CommandFailure),
+    /// An activation failed; `failure` is the nmcli call behind it, if any.
+    Connect { kind: ConnectError, failure: Option<CommandFailure> },
+    Io { context: String, source: io::Error },
+    NoInterface,
+    InterfaceNotFound(String),
+    Usage(String),
+    /// The config file exists but could not be parsed.
+    Config { path: String, message: String },
+    DBus { context: &'static str, source: Box<zbus::Error> },
+    /// Talking to the background daemon failed, or it sent an error we can't map back.
+    Daemon(String),
+}
+
+#[derive(Debug)]
+pub struct CommandFailure {
+    pub command: String,
+    pub args: Vec<String>,
+    pub status: Option<i32>,
+    pub stderr: String,
+}
+
+// Arguments whose following value is a secret and must never be echoed back
+const SECRET_ARGS: &[&str] = &["password", "wep-key0", "802-11-wireless-security.psk", "802-1x.password"];
+
+impl CommandFailure {
+    pub fn new(command: &str, args: &[&str], status: Option<i32>, stderr: String) -> Self {
+        let mut redacted = Vec::with_capacity(args.len());
+        let mut hide_next = false;
+        for arg in args {
+            redacted.push(if hide_next { "********".to_string() } else { arg.to_string() });
+            hide_next = SECRET_ARGS.contains(arg);
+        }
+        Self {
+            command: command.to_string(),
+            args: redacted,
+            status,
+            stderr,
+        }
+    }
+}
+
+impl fmt::Display for CommandFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{} {}' ", self.command, self.args.join(" "))?;
+        match self.status {
+            Some(code) => write!(f, "exited with status {}", code)?,
+            None => write!(f, "was terminated by a signal")?,
+        }
+        if !self.stderr.is_empty() {
+            write!(f, ": {}", self.stderr)?;
+        }
+        Ok(())
+    }
+}
+
+// Process exit codes for non-interactive mode, documented in the README
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_NO_INTERFACE: i32 = 3;
+pub const EXIT_DBUS: i32 = 4;
+pub const EXIT_DAEMON: i32 = 5;
+pub const EXIT_COMMAND_NOT_FOUND: i32 = 127;
+
+impl Error {
+    /// Stable identifier used in JSON output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Spawn { .. } => "spawn",
+            Error::Command(_) => "command",
+            Error::Connect { .. } => "connect",
+            Error::Io { .. } => "io",
+            Error::NoInterface => "no_interface",
+            Error::InterfaceNotFound(_) => "interface_not_found",
+            Error::Usage(_) => "usage",
+            Error::Config { .. } => "config",
+            Error::DBus { .. } => "dbus",
+            Error::Daemon(_) => "daemon",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Spawn { source, .. } if source.kind() == io::ErrorKind::NotFound => {
+                EXIT_COMMAND_NOT_FOUND
+            }
+            Error::Spawn { .. } | Error::Command(_) | Error::Io { .. } => EXIT_FAILURE,
+            Error::Connect { kind, .. } => kind.exit_code(),
+            Error::NoInterface | Error::InterfaceNotFound(_) => EXIT_NO_INTERFACE,
+            Error::Usage(_) | Error::Config { .. } => EXIT_USAGE,
+            Error::DBus { .. } => EXIT_DBUS,
+            Error::Daemon(_) => EXIT_DAEMON,
+        }
+    }
+
+    /// JSON form used for `--json` output and by the daemon protocol.
+    pub fn to_json(&self) -> Value {
+        let mut error = json!({
+            "kind": self.kind(),
+            "message": self.to_string(),
+            "exit_code": self.exit_code(),
+        });
+        let failure = match self {
+            Error::Command(failure) => Some(failure),
+            Error::Connect { kind, failure } => {
+                error["reason"] = json!(kind.code());
+                failure.as_ref()
+            }
+            Error::InterfaceNotFound(iface) => {
+                error["interface"] = json!(iface);
+                None
+            }
+            _ => None,
+        };
+        if let Some(failure) = failure {
+            error["command"] = json!(failure.command);
+            error["args"] = json!(failure.args);
+            error["status"] = json!(failure.status);
+            error["stderr"] = json!(failure.stderr);
+        }
+        error
+    }
+
+    /// Rebuilds an error received from the daemon. Kinds that carry OS-level
+    /// sources (spawn, io, dbus) come back as `Error::Daemon`.
+    pub fn from_json(value: &Value) -> Self {
+        let text = |key: &str| value[key].as_str().unwrap_or_default().to_string();
+        let failure = || {
+            value["command"].as_str().map(|command| CommandFailure {
+                command: command.to_string(),
+                args: value["args"]
+                    .as_array()
+                    .map(|a| a.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+                    .unwrap_or_default(),
+                status: value["status"].as_i64().map(|c| c as i32),
+                stderr: text("stderr"),
+            })
+        };
+
+        match value["kind"].as_str().unwrap_or_default() {
+            "command" => match failure() {
+                Some(failure) => Error::Command(failure),
+                None => Error::Daemon(text("message")),
+            },
+            "connect" => Error::Connect {
+                kind: ConnectError::from_code(value["reason"].as_str().unwrap_or_default(), &text("message")),
+                failure: failure(),
+            },
+            "no_interface" => Error::NoInterface,
+            "interface_not_found" => Error::InterfaceNotFound(text("interface")),
+            "usage" => Error::Usage(text("message")),
+            _ => Error::Daemon(text("message")),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Spawn { command, source } => write!(f, "Cannot run '{}': {}", command, source),
+            Error::Command(failure) => write!(f, "{}", failure),
+            // Unclassified failures are only useful with the command behind them
+            Error::Connect { kind: ConnectError::Other(_), failure: Some(failure) } => {
+                write!(f, "{}", failure)
+            }
+            Error::Connect { kind, .. } => write!(f, "{}", kind),
+            Error::NoInterface => write!(f, "No Wi-Fi interface found."),
+            Error::InterfaceNotFound(iface) => {
+                write!(f, "Interface '{}' not found among Wi-Fi devices.", iface)
+            }
+            Error::Usage(msg) => write!(f, "{}", msg),
+            Error::Config { path, message } => write!(f, "Invalid config file {}: {}", path, message),
+            Error::Io { context, source } => write!(f, "{}: {}", context, source),
+            Error::DBus { context, source } => write!(f, "{}: {}", context, source),
+            Error::Daemon(msg) => write!(f, "Daemon: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Spawn { source, .. } | Error::Io { source, .. } => Some(source),
+            Error::DBus { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Why an activation failed, classified from nmcli's exit code and the
+/// NetworkManager state reason it prints, so the UI can react to each case.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectError {
+    BadSecrets,
+    NotFound,
+    Timeout,
+    Dhcp,
+    DeviceUnavailable,
+    PermissionDenied,
+    RadioOff,
+    Other(String),
+}
+
+// nmcli exit codes, see nmcli(1) "EXIT STATUS"
+const NMCLI_EXIT_TIMEOUT: i32 = 3;
+const NMCLI_EXIT_ACTIVATION_FAILED: i32 = 4;
+const NMCLI_EXIT_NM_NOT_RUNNING: i32 = 8;
+const NMCLI_EXIT_NOT_FOUND: i32 = 10;
+
+impl ConnectError {
+    /// Stable identifier used in JSON output.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ConnectError::BadSecrets => "bad_secrets",
+            ConnectError::NotFound => "not_found",
+            ConnectError::Timeout => "timeout",
+            ConnectError::Dhcp => "dhcp",
+            ConnectError::DeviceUnavailable => "device_unavailable",
+            ConnectError::PermissionDenied => "permission_denied",
+            ConnectError::RadioOff => "radio_off",
+            ConnectError::Other(_) => "other",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ConnectError::Other(_) => 10,
+            ConnectError::BadSecrets => 11,
+            ConnectError::NotFound => 12,
+            ConnectError::Timeout => 13,
+            ConnectError::Dhcp => 14,
+            ConnectError::DeviceUnavailable => 15,
+            ConnectError::PermissionDenied => 16,
+            ConnectError::RadioOff => 17,
+        }
+    }
+
+    pub fn from_code(code: &str, message: &str) -> Self {
+        match code {
+            "bad_secrets" => ConnectError::BadSecrets,
+            "not_found" => ConnectError::NotFound,
+            "timeout" => ConnectError::Timeout,
+            "dhcp" => ConnectError::Dhcp,
+            "device_unavailable" => ConnectError::DeviceUnavailable,
+            "permission_denied" => ConnectError::PermissionDenied,
+            "radio_off" => ConnectError::RadioOff,
+            _ => ConnectError::Other(message.to_string()),
+        }
+    }
+
+    /// Classifies a failed `nmcli dev wifi connect` by its exit status, then
+    /// by the NetworkManager reason nmcli prints for it. The radio being off
+    /// can't be told apart from here; the caller checks for that itself.
+    pub fn from_nmcli(code: Option<i32>, message: &str) -> Self {
+        let reason = message.lines().next().unwrap_or_default();
+        let reason = reason.trim_start_matches("Error: ");
+        let has = |phrases: &[&str]| phrases.iter().any(|p| reason.contains(p));
+
+        // polkit refusals can come with any status
+        if has(&["Not authorized to control networking", "Insufficient privileges"]) {
+            return ConnectError::PermissionDenied;
+        }
+        match code {
+            Some(NMCLI_EXIT_TIMEOUT) => ConnectError::Timeout,
+            Some(NMCLI_EXIT_NOT_FOUND) if reason.starts_with("Device ") || has(&["No Wi-Fi device found"]) => {
+                ConnectError::DeviceUnavailable
+            }
+            Some(NMCLI_EXIT_NOT_FOUND) => ConnectError::NotFound,
+            Some(NMCLI_EXIT_ACTIVATION_FAILED) if has(SECRETS_REASONS) => ConnectError::BadSecrets,
+            Some(NMCLI_EXIT_ACTIVATION_FAILED) if has(DHCP_REASONS) => ConnectError::Dhcp,
+            Some(NMCLI_EXIT_ACTIVATION_FAILED) if has(&["The Wi-Fi network could not be found"]) => {
+                ConnectError::NotFound
+            }
+            Some(NMCLI_EXIT_ACTIVATION_FAILED) if has(DEVICE_REASONS) => ConnectError::DeviceUnavailable,
+            Some(NMCLI_EXIT_NM_NOT_RUNNING) => ConnectError::DeviceUnavailable,
+            _ => ConnectError::Other(reason.to_string()),
+        }
+    }
+}
+
+// NetworkManager's device state reasons as nmcli prints them after
+// "Connection activation failed: (N) "; a wrong PSK usually ends as "(7)".
+const SECRETS_REASONS: &[&str] = &[
+    "Secrets were required, but not provided",
+    "802.1X supplicant disconnected",
+    "802.1X supplicant failed",
+    "802.1X supplicant took too long to authenticate",
+    "802-11-wireless-security.psk: property is invalid",
+    "802-11-wireless-security.wep-key0: property is invalid",
+];
+const DHCP_REASONS: &[&str] = &[
+    "DHCP client failed to start",
+    "DHCP client error",
+    "DHCP client failed",
+    "IP configuration could not be reserved",
+];
+const DEVICE_REASONS: &[&str] = &[
+    "device is not available",
+    "The device could not be readied for configuration",
+    "The device was removed",
+    "The device is now unmanaged",
+];
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectError::BadSecrets => write!(f, "Wrong password"),
+            ConnectError::NotFound => write!(f, "Network not in range, try rescanning (r)"),
+            ConnectError::Timeout => write!(f, "Timed out waiting for the network"),
+            ConnectError::Dhcp => write!(f, "Associated, but got no IP address (DHCP failed)"),
+            ConnectError::DeviceUnavailable => write!(f, "Wi-Fi device is unavailable"),
+            ConnectError::PermissionDenied => {
+                write!(f, "Not authorized to manage networks (check polkit rules)")
+            }
+            ConnectError::RadioOff => write!(f, "Wi-Fi is off, enable it with 'nmcli radio wifi on'"),
+            ConnectError::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_nmcli_failures() {
+        let cases: &[(i32, &str, ConnectError)] = &[
+            (4, "Error: Connection activation failed: (7) Secrets were required, but not provided.\n\
+                 Hint: use 'journalctl -xe NM_CONNECTION=1f0a NM_DEVICE=wlan0' to get more details.",
+                ConnectError::BadSecrets),
+            (4, "Error: Connection activation failed: Secrets were required, but not provided.", ConnectError::BadSecrets),
+            (4, "Error: Connection activation failed: (9) 802.1X supplicant disconnected.", ConnectError::BadSecrets),
+            (4, "Error: Failed to add/activate new connection: 802-11-wireless-security.psk: property is invalid",
+                ConnectError::BadSecrets),
+            (4, "Error: Connection activation failed: (17) DHCP client failed to start.", ConnectError::Dhcp),
+            (4, "Error: Connection activation failed: (5) IP configuration could not be reserved (no available address, timeout, etc.).",
+                ConnectError::Dhcp),
+            (4, "Error: Connection activation failed: (53) The Wi-Fi network could not be found.", ConnectError::NotFound),
+            (4, "Error: Failed to add/activate new connection: Connection 'Home' is not available on device wlan0 because device is not available",
+                ConnectError::DeviceUnavailable),
+            (4, "Error: Failed to add/activate new connection: Not authorized to control networking.",
+                ConnectError::PermissionDenied),
+            (1, "Error: Insufficient privileges.", ConnectError::PermissionDenied),
+            (10, "Error: No network with SSID 'Home' found.", ConnectError::NotFound),
+            (10, "Error: Device 'wlan9' not found.", ConnectError::DeviceUnavailable),
+            (10, "Error: No Wi-Fi device found.", ConnectError::DeviceUnavailable),
+            (3, "Error: Timeout expired (90 seconds)", ConnectError::Timeout),
+            (8, "Error: NetworkManager is not running.", ConnectError::DeviceUnavailable),
+        ];
+        for (code, stderr, expected) in cases {
+            assert_eq!(&ConnectError::from_nmcli(Some(*code), stderr), expected, "{stderr}");
+        }
+    }
+
+    #[test]
+    fn unrecognised_failures_keep_the_message() {
+        // "password" in a message is no longer taken as a wrong password
+        assert_eq!(
+            ConnectError::from_nmcli(Some(2), "Error: invalid 'password' argument."),
+            ConnectError::Other("invalid 'password' argument.".to_string())
+        );
+        assert_eq!(
+            ConnectError::from_nmcli(Some(4), "Error: Connection activation failed: (10) 802.1X supplicant configuration failed."),
+            ConnectError::Other("Connection activation failed: (10) 802.1X supplicant configuration failed.".to_string())
+        );
+        assert_eq!(ConnectError::from_nmcli(None, ""), ConnectError::Other(String::new()));
+    }
+}
@@ -0,0 +1,73 @@
+// Search and filter chips for the TUI network list.
+//
+// The filter only narrows down the results of the last scan, so typing a
+// query or toggling a chip never re-scans.
+
+use crate::{config, Band, Network, SecurityFilter};
+
+#[derive(Default)]
+pub struct Filter {
+    /// Fuzzy SSID search typed after `/`
+    pub query: String,
+    pub open_only: bool,
+    pub saved_only: bool,
+    pub band_5ghz_only: bool,
+    pub min_signal: u8,
+}
+
+impl Filter {
+    pub fn is_active(&self) -> bool {
+        !self.chips().is_empty()
+    }
+
+    pub fn matches(&self, net: &Network) -> bool {
+        fuzzy_match(&self.query, &net.ssid.to_string()).is_some()
+            && (!self.open_only || SecurityFilter::Open.matches(&net.security))
+            && (!self.saved_only || net.saved)
+            && (!self.band_5ghz_only || Band::of(net.freq) == Band::Ghz5)
+            && net.signal >= self.min_signal
+    }
+
+    /// Short labels for everything that is switched on.
+    pub fn chips(&self) -> Vec<String> {
+        let mut chips = Vec::new();
+        if !self.query.is_empty() {
+            chips.push(format!("/{}", self.query));
+        }
+        if self.open_only {
+            chips.push("open".to_string());
+        }
+        if self.saved_only {
+            chips.push("saved".to_string());
+        }
+        if self.band_5ghz_only {
+            chips.push("5 GHz".to_string());
+        }
+        if self.min_signal > 0 {
+            chips.push(format!("min {}%", self.min_signal));
+        }
+        chips
+    }
+
+    /// Steps the minimum signal through the `ui.signal_thresholds` and back to 0.
+    pub fn cycle_min_signal(&mut self) {
+        let steps = config::get().ui.signal_thresholds;
+        self.min_signal = steps.into_iter().find(|&step| step > self.min_signal).unwrap_or(0);
+    }
+
+    pub fn clear(&mut self) {
+        *self = Filter::default();
+    }
+}
+
+/// Char positions in `text` of the characters of `query`, in order and
+/// ignoring case, or `None` if `text` doesn't contain them all.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<Vec<usize>> {
+    let mut positions = Vec::new();
+    let mut chars = text.chars().enumerate();
+    for wanted in query.chars() {
+        let (pos, _) = chars.find(|(_, c)| c.to_lowercase().eq(wanted.to_lowercase()))?;
+        positions.push(pos);
+    }
+    Some(positions)
+}
@@ -0,0 +1,300 @@
+// Access points seen in earlier scans.
+//
+// Every scan is merged into `$XDG_STATE_HOME/wifi_menu/history.json` (default
+// `~/.local/state/wifi_menu/history.json`): per BSSID the SSID and security it
+// last had, its best signal, and when it was first and last seen. Writers
+// hold a lock on `history.json.lock` while they re-read, merge and rename the
+// file into place, so the TUI, `--list` and the daemon can all add to it.
+// Access points unseen for `history.keep_days` are dropped. Unlike `trend`,
+// this outlives the session.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::ssid::Ssid;
+use crate::{config, unix_now, Network};
+
+const FILE_NAME: &str = "history.json";
+
+/// Scans are merged in memory and written at most this often, so the daemon
+/// and the TUI don't rewrite the whole file every few seconds.
+const WRITE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Seen for the first time this recently, an access point counts as new.
+pub const NEW_FOR_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Sighting {
+    pub bssid: String,
+    pub ssid: Ssid,
+    pub security: String,
+    pub best_signal: u8,
+    #[serde(default)]
+    pub chan: u32,
+    /// Unix seconds
+    pub first_seen: u64,
+    pub last_seen: u64,
+}
+
+pub fn path() -> Option<PathBuf> {
+    config::state_dir().map(|dir| dir.join(FILE_NAME))
+}
+
+/// Everything recorded so far, most recently seen first.
+pub fn load() -> Result<Vec<Sighting>, Error> {
+    match path() {
+        Some(path) => load_from(&path),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn load_from(path: &Path) -> Result<Vec<Sighting>, Error> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(source) => {
+            return Err(Error::Io {
+                context: format!("Cannot read {}", path.display()),
+                source,
+            })
+        }
+    };
+    let mut sightings: Vec<Sighting> = serde_json::from_str(&text).map_err(|e| Error::Io {
+        context: format!("Cannot parse {}", path.display()),
+        source: e.into(),
+    })?;
+    sightings.sort_by(|a, b| b.last_seen.cmp(&a.last_seen).then_with(|| a.bssid.cmp(&b.bssid)));
+    Ok(sightings)
+}
+
+/// Adds one scan to the history. The first scan of a process is written
+/// right away, later ones at most every `WRITE_INTERVAL`, and `flush` writes
+/// whatever is still held back. Scans that fail to be written stay held back
+/// for the next attempt, so callers carry on regardless.
+pub fn record(access_points: &[Network]) -> Result<(), Error> {
+    if !config::get().history.enabled || access_points.is_empty() {
+        return Ok(());
+    }
+    let now = unix_now();
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    for ap in access_points.iter().filter(|ap| !ap.bssid.is_empty()) {
+        let sighting = Sighting {
+            bssid: ap.bssid.clone(),
+            ssid: ap.ssid.clone(),
+            security: ap.security.clone(),
+            best_signal: ap.signal,
+            chan: ap.chan,
+            first_seen: now,
+            last_seen: now,
+        };
+        merge(&mut pending.sightings, sighting);
+    }
+    if pending.written.is_some_and(|at| at.elapsed() < WRITE_INTERVAL) {
+        return Ok(());
+    }
+    write(&mut pending)
+}
+
+/// Writes the scans `record` has held back.
+pub fn flush() -> Result<(), Error> {
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    if pending.sightings.is_empty() {
+        return Ok(());
+    }
+    write(&mut pending)
+}
+
+struct Pending {
+    sightings: BTreeMap<String, Sighting>,
+    written: Option<Instant>,
+}
+
+static PENDING: Mutex<Pending> = Mutex::new(Pending {
+    sightings: BTreeMap::new(),
+    written: None,
+});
+
+fn merge(into: &mut BTreeMap<String, Sighting>, later: Sighting) {
+    match into.get_mut(&later.bssid) {
+        Some(sighting) => {
+            sighting.ssid = later.ssid;
+            sighting.security = later.security;
+            sighting.best_signal = sighting.best_signal.max(later.best_signal);
+            sighting.chan = later.chan;
+            sighting.first_seen = sighting.first_seen.min(later.first_seen);
+            sighting.last_seen = sighting.last_seen.max(later.last_seen);
+        }
+        None => {
+            into.insert(later.bssid.clone(), later);
+        }
+    }
+}
+
+fn write(pending: &mut Pending) -> Result<(), Error> {
+    pending.written = Some(Instant::now());
+    let Some(path) = path() else {
+        pending.sightings.clear();
+        return Ok(());
+    };
+    let oldest = unix_now().saturating_sub(config::get().history.keep_days * 24 * 60 * 60);
+    write_to(&path, &pending.sightings, oldest)?;
+    // Only now, so a failed write is retried with the next one
+    pending.sightings.clear();
+    Ok(())
+}
+
+/// Merges `sightings` into the file at `path`, dropping access points last
+/// seen before `oldest`.
+fn write_to(path: &Path, sightings: &BTreeMap<String, Sighting>, oldest: u64) -> Result<(), Error> {
+    let io_error = |source| Error::Io {
+        context: format!("Cannot write {}", path.display()),
+        source,
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(io_error)?;
+    }
+
+    // Held until the rename, so writers in other processes take turns and
+    // none of them drops what another just merged
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path.with_extension("json.lock"))
+        .map_err(io_error)?;
+    lock.lock().map_err(io_error)?;
+
+    // A file that can't be read is left alone rather than replaced by this scan
+    let mut merged: BTreeMap<String, Sighting> = load_from(path)?
+        .into_iter()
+        .filter(|s| s.last_seen >= oldest)
+        .map(|s| (s.bssid.clone(), s))
+        .collect();
+    for sighting in sightings.values() {
+        merge(&mut merged, sighting.clone());
+    }
+
+    let sightings: Vec<Sighting> = merged.into_values().collect();
+    let json = serde_json::to_string(&sightings).map_err(|e| io_error(e.into()))?;
+    // Readers never see a half-written file
+    let tmp = path.with_extension(format!("json.{}", std::process::id()));
+    fs::write(&tmp, json).map_err(io_error)?;
+    fs::rename(&tmp, path).map_err(io_error)
+}
+
+/// "now", "5m ago", "3h ago" or "12d ago".
+pub fn ago(secs: u64) -> String {
+    let elapsed = unix_now().saturating_sub(secs);
+    match elapsed {
+        0..60 => "now".to_string(),
+        60..3600 => format!("{}m ago", elapsed / 60),
+        3600..86_400 => format!("{}h ago", elapsed / 3600),
+        _ => format!("{}d ago", elapsed / 86_400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sighting(bssid: &str, ssid: &str, signal: u8, seen: u64) -> Sighting {
+        Sighting {
+            bssid: bssid.to_string(),
+            ssid: Ssid::from(ssid),
+            security: "WPA2".to_string(),
+            best_signal: signal,
+            chan: 6,
+            first_seen: seen,
+            last_seen: seen,
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wifi_menu-history-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join(FILE_NAME)
+    }
+
+    #[test]
+    fn merge_keeps_the_best_signal_and_the_widest_span() {
+        let mut sightings = BTreeMap::new();
+        merge(&mut sightings, sighting("AA:01", "Home", 70, 1000));
+        merge(&mut sightings, Sighting {
+            security: "WPA3".to_string(),
+            chan: 11,
+            ..sighting("AA:01", "Home 2", 40, 2000)
+        });
+        merge(&mut sightings, sighting("AA:01", "Home 2", 55, 500));
+        merge(&mut sightings, sighting("AA:02", "Cafe", 30, 1500));
+
+        assert_eq!(sightings.len(), 2);
+        let home = &sightings["AA:01"];
+        // SSID, security and channel come from the scan merged last
+        assert_eq!(home.ssid, Ssid::from("Home 2"));
+        assert_eq!(home.security, "WPA2");
+        assert_eq!(home.chan, 6);
+        assert_eq!(home.best_signal, 70);
+        assert_eq!((home.first_seen, home.last_seen), (500, 2000));
+        assert_eq!(sightings["AA:02"].best_signal, 30);
+    }
+
+    #[test]
+    fn write_merges_into_the_file_and_prunes_old_access_points() {
+        let path = temp_path("prune");
+        let first: BTreeMap<_, _> = [
+            sighting("AA:01", "Home", 60, 1000),
+            sighting("AA:02", "Gone", 50, 100),
+        ]
+        .into_iter()
+        .map(|s| (s.bssid.clone(), s))
+        .collect();
+        write_to(&path, &first, 0).unwrap();
+        assert_eq!(load_from(&path).unwrap().len(), 2);
+
+        let later: BTreeMap<_, _> = [sighting("AA:01", "Home", 80, 3000), sighting("AA:03", "New", 40, 2000)]
+            .into_iter()
+            .map(|s| (s.bssid.clone(), s))
+            .collect();
+        write_to(&path, &later, 500).unwrap();
+
+        let sightings = load_from(&path).unwrap();
+        // Most recently seen first, and AA:02 was last seen before the cutoff
+        let bssids: Vec<&str> = sightings.iter().map(|s| s.bssid.as_str()).collect();
+        assert_eq!(bssids, ["AA:01", "AA:03"]);
+        assert_eq!(sightings[0].best_signal, 80);
+        assert_eq!((sightings[0].first_seen, sightings[0].last_seen), (1000, 3000));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn unreadable_file_is_left_alone() {
+        let path = temp_path("unreadable");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "not json").unwrap();
+        let scan: BTreeMap<_, _> = [("AA:01".to_string(), sighting("AA:01", "Home", 60, 1000))].into();
+
+        assert!(write_to(&path, &scan, 0).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "not json");
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn ago_rounds_down_to_the_largest_unit() {
+        let now = unix_now();
+        assert_eq!(ago(now), "now");
+        assert_eq!(ago(now + 30), "now");
+        assert_eq!(ago(now - 50), "now");
+        assert_eq!(ago(now - 60), "1m ago");
+        assert_eq!(ago(now - 3590), "59m ago");
+        assert_eq!(ago(now - 3600), "1h ago");
+        assert_eq!(ago(now - 86_390), "23h ago");
+        assert_eq!(ago(now - 86_400), "1d ago");
+        assert_eq!(ago(now - 40 * 86_400), "40d ago");
+    }
+}
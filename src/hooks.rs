@@ -0,0 +1,374 @@
+// Event hooks.
+//
+// Every executable in `$XDG_CONFIG_HOME/wifi_menu/hooks.d/` (default
+// `~/.config/wifi_menu/hooks.d/`) runs when wifi_menu connects, disconnects,
+// fails to connect, or sees a device roam to another access point. Hooks run
+// in the background with a timeout, so a hung script can't block the UI.
+// The same events drive desktop notifications when `--notify` is on.
+//
+// A hook can limit itself with comment lines near the top of the file:
+//
+//     # wifi_menu-ssid: Home*
+//     # wifi_menu-event: connect, roam
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::error::{ConnectError, Error};
+use crate::{config, daemon, notify, run_command, Network};
+
+const HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+// Rules are only looked for in the first few KiB, so binaries are cheap to skip
+const RULES_SCAN_BYTES: u64 = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Event {
+    Connect,
+    Disconnect,
+    Failure,
+    Roam,
+}
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::Connect => "connect",
+            Event::Disconnect => "disconnect",
+            Event::Failure => "failure",
+            Event::Roam => "roam",
+        }
+    }
+}
+
+struct HookEvent {
+    event: Event,
+    interface: String,
+    ssid: String,
+    bssid: String,
+    ip4: Option<String>,
+    error: Option<String>,
+}
+
+/// (ssid, bssid) each device was last seen associated with.
+static LINKS: Mutex<BTreeMap<String, Option<(String, String)>>> = Mutex::new(BTreeMap::new());
+static RUNNING: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+fn hooks_dir() -> Option<PathBuf> {
+    Some(config::config_dir()?.join("hooks.d"))
+}
+
+/// Records a successful connection made by wifi_menu.
+pub fn connected(interface: &str, ssid: &str, bssid: &str) {
+    let bssid = match bssid {
+        "" => current_bssid(interface).unwrap_or_default(),
+        pinned => pinned.to_string(),
+    };
+    let link = Some((ssid.to_string(), bssid.clone()));
+    // The daemon may already have observed it while nmcli was still running
+    if record(interface, link.clone()) == Some(link) {
+        return;
+    }
+    notify::connected(ssid);
+    fire(HookEvent {
+        event: Event::Connect,
+        interface: interface.to_string(),
+        ssid: ssid.to_string(),
+        bssid,
+        ip4: current_ip4(interface),
+        error: None,
+    });
+}
+
+/// Records a disconnect made by wifi_menu; `ssid` is the connection that was active.
+pub fn disconnected(interface: &str, ssid: Option<String>) {
+    let previous = record(interface, None).flatten();
+    let (ssid, bssid) = match (ssid, previous) {
+        (_, Some(link)) => link,
+        (Some(ssid), None) => (ssid, String::new()),
+        (None, None) => (String::new(), String::new()),
+    };
+    notify::disconnected(&ssid);
+    fire(HookEvent {
+        event: Event::Disconnect,
+        interface: interface.to_string(),
+        ssid,
+        bssid,
+        ip4: None,
+        error: None,
+    });
+}
+
+pub fn failed(interface: &str, ssid: &str, bssid: &str, error: &Error) {
+    let reason = match error {
+        Error::Connect { kind, .. } => kind.code().to_string(),
+        other => other.kind().to_string(),
+    };
+    notify::failed(ssid, &error.to_string());
+    fire(HookEvent {
+        event: Event::Failure,
+        interface: interface.to_string(),
+        ssid: ssid.to_string(),
+        bssid: bssid.to_string(),
+        ip4: None,
+        error: Some(reason),
+    });
+}
+
+/// Compares what a device is associated with now against the last time it
+/// was seen, firing connect, disconnect or roam for changes made outside
+/// wifi_menu. The first sighting of a device only records it.
+pub fn observe(interface: &str, active: Option<&Network>) {
+    // A running daemon does the watching, so events aren't reported twice
+    if daemon::in_use() {
+        return;
+    }
+    notify::signal(interface, active);
+    let link = active.map(|n| (n.ssid.to_string(), n.bssid.clone()));
+    let Some(previous) = record(interface, link.clone()) else { return };
+    if previous == link {
+        return;
+    }
+
+    let (event, (ssid, bssid)) = match (previous, link) {
+        (Some(prev), Some(now)) if prev.0 == now.0 => (Event::Roam, now),
+        (_, Some(now)) => (Event::Connect, now),
+        (Some(prev), None) => (Event::Disconnect, prev),
+        (None, None) => return,
+    };
+    match event {
+        Event::Connect => notify::connected(&ssid),
+        Event::Disconnect => notify::disconnected(&ssid),
+        // Same network, nothing the user needs to hear about
+        Event::Failure | Event::Roam => {}
+    }
+    fire(HookEvent {
+        event,
+        ip4: if event == Event::Disconnect { None } else { current_ip4(interface) },
+        interface: interface.to_string(),
+        ssid,
+        bssid,
+        error: None,
+    });
+}
+
+/// Stores the new link and returns the previous one, or `None` if the device
+/// wasn't known yet.
+fn record(interface: &str, link: Option<(String, String)>) -> Option<Option<(String, String)>> {
+    LINKS.lock().unwrap_or_else(|e| e.into_inner()).insert(interface.to_string(), link)
+}
+
+/// Whether a connect attempt failing this way is worth reporting. Trying a
+/// secured network without a password only checks for saved secrets.
+pub fn is_reportable(password: &str, error: &Error) -> bool {
+    !(password.is_empty() && matches!(error, Error::Connect { kind: ConnectError::BadSecrets, .. }))
+}
+
+fn current_ip4(interface: &str) -> Option<String> {
+    let output = run_command("nmcli", &["-g", "IP4.ADDRESS", "device", "show", interface]).ok()?;
+    // Several addresses are separated by " | "
+    output.split(" | ").next().map(str::to_string).filter(|addr| !addr.is_empty())
+}
+
+fn current_bssid(interface: &str) -> Option<String> {
+    let output = run_command(
+        "nmcli",
+        &["-g", "ACTIVE,BSSID", "dev", "wifi", "list", "ifname", interface, "--rescan", "no"],
+    )
+    .ok()?;
+    // Terse output escapes the colons inside the BSSID
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("yes:"))
+        .map(|bssid| bssid.replace("\\:", ":"))
+}
+
+struct Rules {
+    ssids: Vec<String>,
+    events: Vec<String>,
+}
+
+impl Rules {
+    fn read(path: &Path) -> Self {
+        let mut rules = Rules { ssids: Vec::new(), events: Vec::new() };
+        let mut bytes = Vec::new();
+        let read = File::open(path).and_then(|f| f.take(RULES_SCAN_BYTES).read_to_end(&mut bytes));
+        if read.is_err() {
+            return rules;
+        }
+        let head = String::from_utf8_lossy(&bytes);
+        for line in head.lines() {
+            let Some(comment) = line.trim_start().strip_prefix('#') else { continue };
+            let comment = comment.trim();
+            if let Some(ssid) = comment.strip_prefix("wifi_menu-ssid:") {
+                rules.ssids.push(ssid.trim().to_string());
+            } else if let Some(events) = comment.strip_prefix("wifi_menu-event:") {
+                rules.events.extend(
+                    events.split([',', ' ']).filter(|e| !e.is_empty()).map(str::to_string),
+                );
+            }
+        }
+        rules
+    }
+
+    fn matches(&self, event: &HookEvent) -> bool {
+        (self.events.is_empty() || self.events.iter().any(|e| e == event.event.name()))
+            && (self.ssids.is_empty() || self.ssids.iter().any(|p| glob_match(p, &event.ssid)))
+    }
+}
+
+/// Matches `text` against a pattern where `*` stands for any run of characters.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else { return false };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No '*' at all
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+fn fire(event: HookEvent) {
+    let Some(dir) = hooks_dir() else { return };
+    let Ok(entries) = fs::read_dir(&dir) else { return };
+    let mut hooks: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            let hidden = path.file_name().and_then(|n| n.to_str()).is_none_or(|n| n.starts_with('.'));
+            let executable =
+                fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
+            !hidden && executable
+        })
+        .collect();
+    // Run in name order like run-parts, so 10-vpn runs before 20-mounts
+    hooks.sort();
+
+    let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+    // The daemon and the TUI fire hooks for as long as they run
+    running.retain(|handle| !handle.is_finished());
+    for hook in hooks {
+        if !Rules::read(&hook).matches(&event) {
+            continue;
+        }
+        let mut command = Command::new(&hook);
+        command
+            .arg(event.event.name())
+            .env("WIFI_MENU_EVENT", event.event.name())
+            .env("WIFI_MENU_INTERFACE", &event.interface)
+            .env("WIFI_MENU_SSID", &event.ssid)
+            .env("WIFI_MENU_BSSID", &event.bssid)
+            .env("WIFI_MENU_IP4", event.ip4.as_deref().unwrap_or(""))
+            .env("WIFI_MENU_ERROR", event.error.as_deref().unwrap_or(""))
+            // Hook output would end up on top of the TUI
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            // So a timeout also stops whatever the hook started
+            .process_group(0);
+        running.push(thread::spawn(move || run_hook(command)));
+    }
+}
+
+fn run_hook(mut command: Command) {
+    let Ok(mut child) = command.spawn() else { return };
+    let deadline = Instant::now() + HOOK_TIMEOUT;
+    while let Ok(None) = child.try_wait() {
+        if Instant::now() >= deadline {
+            // The hook leads its own process group, whose id is its pid
+            let group = format!("-{}", child.id());
+            if run_command("kill", &["-KILL", "--", &group]).is_err() {
+                let _ = child.kill();
+            }
+            let _ = child.wait();
+            return;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Waits for hooks that are still running, so one-shot commands don't exit
+/// from under them.
+pub fn finish() {
+    let handles = std::mem::take(&mut *RUNNING.lock().unwrap_or_else(|e| e.into_inner()));
+    for handle in handles {
+        let _ = handle.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event: Event, ssid: &str) -> HookEvent {
+        HookEvent {
+            event,
+            interface: "wlan0".to_string(),
+            ssid: ssid.to_string(),
+            bssid: String::new(),
+            ip4: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn glob_matches_stars_anywhere() {
+        let cases = [
+            ("Home", "Home", true),
+            ("Home", "Home 5G", false),
+            ("Home*", "Home 5G", true),
+            ("Home*", "Home", true),
+            ("*5G", "Home 5G", true),
+            ("*5G", "Home 5G Guest", false),
+            ("*Guest*", "Home Guest 5G", true),
+            ("H*e*5G", "Home 5G", true),
+            ("H*e*5G", "Hom 5G", false),
+            ("a*a", "a", false),
+            ("*", "", true),
+            ("", "", true),
+            ("", "x", false),
+            ("Café*", "Café Paris", true),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(glob_match(pattern, text), expected, "{pattern:?} vs {text:?}");
+        }
+    }
+
+    #[test]
+    fn rules_limit_events_and_ssids() {
+        let dir = std::env::temp_dir().join(format!("wifi_menu-hooks-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let hook = dir.join("10-vpn");
+        fs::write(
+            &hook,
+            "#!/bin/sh\n# wifi_menu-ssid: Home*\n  #wifi_menu-ssid:Office\n# wifi_menu-event: connect, roam\nexit 0\n",
+        )
+        .unwrap();
+        let rules = Rules::read(&hook);
+        assert_eq!(rules.ssids, ["Home*", "Office"]);
+        assert_eq!(rules.events, ["connect", "roam"]);
+        assert!(rules.matches(&event(Event::Connect, "Home 5G")));
+        assert!(rules.matches(&event(Event::Roam, "Office")));
+        assert!(!rules.matches(&event(Event::Disconnect, "Home 5G")));
+        assert!(!rules.matches(&event(Event::Connect, "Office Guest")));
+
+        fs::write(&hook, "#!/bin/sh\nexit 0\n").unwrap();
+        let rules = Rules::read(&hook);
+        assert!(rules.matches(&event(Event::Failure, "anything")));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
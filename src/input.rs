@@ -0,0 +1,238 @@
+// Single-line text editor used by the password prompt.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::text::Span;
+
+#[derive(Default)]
+pub struct LineEditor {
+    text: String,
+    // Cursor position in chars, not bytes
+    cursor: usize,
+}
+
+impl LineEditor {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn char_count(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+    }
+
+    fn byte_index(&self, char_idx: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(char_idx)
+            .map(|(i, _)| i)
+            .unwrap_or(self.text.len())
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        let idx = self.byte_index(self.cursor);
+        self.text.insert(idx, c);
+        self.cursor += 1;
+    }
+
+    /// Inserts pasted text. Line breaks are dropped, since a pasted password
+    /// usually carries a trailing newline that isn't part of it.
+    pub fn insert_str(&mut self, s: &str) {
+        for c in s.chars().filter(|c| !c.is_control()) {
+            self.insert_char(c);
+        }
+    }
+
+    fn delete_range(&mut self, from: usize, to: usize) {
+        let start = self.byte_index(from);
+        let end = self.byte_index(to);
+        self.text.replace_range(start..end, "");
+        self.cursor = from;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.delete_range(self.cursor - 1, self.cursor);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.char_count() {
+            let cursor = self.cursor;
+            self.delete_range(cursor, cursor + 1);
+        }
+    }
+
+    /// Ctrl-W: delete the word before the cursor, plus any whitespace after it.
+    pub fn delete_word_back(&mut self) {
+        let chars: Vec<char> = self.text.chars().collect();
+        let mut start = self.cursor;
+        while start > 0 && chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        while start > 0 && !chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        self.delete_range(start, self.cursor);
+    }
+
+    /// Applies an editing key. Returns false for keys the editor doesn't handle.
+    pub fn handle_key(&mut self, key: &KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::Char('e') if ctrl => self.cursor = self.char_count(),
+            KeyCode::Char('b') if ctrl => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Char('f') if ctrl => self.cursor = (self.cursor + 1).min(self.char_count()),
+            KeyCode::Char('u') if ctrl => self.delete_range(0, self.cursor),
+            KeyCode::Char('k') if ctrl => {
+                let end = self.char_count();
+                let cursor = self.cursor;
+                self.delete_range(cursor, end);
+            }
+            KeyCode::Char('w') if ctrl => self.delete_word_back(),
+            KeyCode::Char('h') if ctrl => self.backspace(),
+            KeyCode::Backspace if alt || ctrl => self.delete_word_back(),
+            KeyCode::Char(_) if ctrl || alt => return false,
+            KeyCode::Char(c) => self.insert_char(c),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.char_count()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.char_count(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Returns the part of the text that fits in `width` columns, scrolled so
+    /// the cursor stays visible, and the cursor column within it.
+    pub fn visible(&self, width: usize, masked: bool) -> (String, usize) {
+        let shown: Vec<String> = self
+            .text
+            .chars()
+            .map(|c| if masked { "*".to_string() } else { c.to_string() })
+            .collect();
+        let col = |s: &str| Span::raw(s).width();
+
+        // Walk back from the cursor until the window is full
+        let width = width.max(1);
+        let mut start = self.cursor;
+        let mut used = 1; // room for the cursor itself
+        while start > 0 && used + col(&shown[start - 1]) <= width {
+            start -= 1;
+            used += col(&shown[start]);
+        }
+
+        let mut out = String::new();
+        let mut cursor_col = 0;
+        let mut total = 0;
+        for (i, s) in shown.iter().enumerate().skip(start) {
+            if i == self.cursor {
+                cursor_col = total;
+            }
+            if total + col(s) > width {
+                break;
+            }
+            total += col(s);
+            out.push_str(s);
+        }
+        if self.cursor >= shown.len() {
+            cursor_col = total;
+        }
+        (out, cursor_col)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(text: &str) -> LineEditor {
+        let mut editor = LineEditor::default();
+        editor.insert_str(text);
+        editor
+    }
+
+    fn press(editor: &mut LineEditor, code: KeyCode, modifiers: KeyModifiers) {
+        editor.handle_key(&KeyEvent::new(code, modifiers));
+    }
+
+    #[test]
+    fn edits_multibyte_text_by_char() {
+        let mut e = editor("añ日🙂");
+        assert_eq!(e.char_count(), 4);
+
+        e.backspace();
+        assert_eq!(e.text(), "añ日");
+        press(&mut e, KeyCode::Left, KeyModifiers::NONE);
+        press(&mut e, KeyCode::Left, KeyModifiers::NONE);
+        e.insert_char('é');
+        assert_eq!(e.text(), "aéñ日");
+        e.delete();
+        assert_eq!(e.text(), "aé日");
+        e.backspace();
+        assert_eq!(e.text(), "a日");
+
+        press(&mut e, KeyCode::Home, KeyModifiers::NONE);
+        e.delete();
+        assert_eq!(e.text(), "日");
+        e.backspace();
+        assert_eq!(e.text(), "日");
+        press(&mut e, KeyCode::End, KeyModifiers::NONE);
+        e.delete();
+        assert_eq!(e.text(), "日");
+    }
+
+    #[test]
+    fn cursor_stays_within_the_text() {
+        let mut e = editor("日本");
+        press(&mut e, KeyCode::Right, KeyModifiers::NONE);
+        e.insert_char('x');
+        assert_eq!(e.text(), "日本x");
+        for _ in 0..5 {
+            press(&mut e, KeyCode::Char('b'), KeyModifiers::CONTROL);
+        }
+        e.insert_char('y');
+        assert_eq!(e.text(), "y日本x");
+    }
+
+    #[test]
+    fn kills_words_and_lines() {
+        let mut e = editor("pässwörd  mit  Ümlaut ");
+        e.delete_word_back();
+        assert_eq!(e.text(), "pässwörd  mit  ");
+        press(&mut e, KeyCode::Backspace, KeyModifiers::ALT);
+        assert_eq!(e.text(), "pässwörd  ");
+
+        press(&mut e, KeyCode::Char('a'), KeyModifiers::CONTROL);
+        press(&mut e, KeyCode::Char('f'), KeyModifiers::CONTROL);
+        press(&mut e, KeyCode::Char('k'), KeyModifiers::CONTROL);
+        assert_eq!(e.text(), "p");
+        e.insert_str("ö");
+        press(&mut e, KeyCode::Char('u'), KeyModifiers::CONTROL);
+        assert_eq!(e.text(), "");
+    }
+
+    #[test]
+    fn paste_drops_line_breaks() {
+        assert_eq!(editor("secret\r\n").text(), "secret");
+    }
+
+    #[test]
+    fn visible_keeps_the_cursor_in_view() {
+        let e = editor("日本語パスワード");
+        let (shown, col) = e.visible(6, false);
+        // One column stays free for the cursor at the end
+        assert_eq!(shown, "ード");
+        assert_eq!(col, 4);
+        let (shown, col) = e.visible(6, true);
+        assert_eq!(shown, "*****");
+        assert_eq!(col, 5);
+    }
+}
@@ -0,0 +1,581 @@
+// Key bindings.
+//
+// Keys map to named actions, separately for each mode of the TUI. The
+// `[keys.<mode>]` sections of the config file replace the keys of individual
+// actions, e.g. `quit = ["q", "Ctrl-c"]`; everything else keeps its default.
+
+use std::collections::BTreeMap;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    Browse,
+    Password,
+    ActionMenu,
+    Confirm,
+    Help,
+    Search,
+    Channels,
+    Waypoint,
+    History,
+}
+
+impl Mode {
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Browse => "browse",
+            Mode::Password => "password",
+            Mode::ActionMenu => "action_menu",
+            Mode::Confirm => "confirm",
+            Mode::Help => "help",
+            Mode::Search => "search",
+            Mode::Channels => "channels",
+            Mode::Waypoint => "waypoint",
+            Mode::History => "history",
+        }
+    }
+
+    /// Modes with a text field, where plain characters are typed rather than bound.
+    fn takes_text(self) -> bool {
+        matches!(self, Mode::Password | Mode::Search | Mode::Waypoint)
+    }
+
+    /// Heading for the help overlay.
+    pub fn title(self) -> &'static str {
+        match self {
+            Mode::Browse => "Network list",
+            Mode::Password => "Password prompt",
+            Mode::ActionMenu => "Action menu",
+            Mode::Confirm => "Confirmation",
+            Mode::Help => "Help",
+            Mode::Search => "Search",
+            Mode::Channels => "Channel view",
+            Mode::Waypoint => "Waypoint prompt",
+            Mode::History => "History",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    Next,
+    Prev,
+    Rescan,
+    CycleInterface,
+    Connect,
+    Submit,
+    TogglePassword,
+    Select,
+    Cancel,
+    Confirm,
+    Help,
+    PageDown,
+    PageUp,
+    Search,
+    NextMatch,
+    PrevMatch,
+    FilterOpen,
+    FilterSaved,
+    #[serde(rename = "filter_5ghz")]
+    Filter5Ghz,
+    FilterSignal,
+    ClearFilter,
+    CycleSort,
+    ReverseSort,
+    Details,
+    Channels,
+    Waypoint,
+    History,
+}
+
+impl Action {
+    fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Next => "next",
+            Action::Prev => "prev",
+            Action::Rescan => "rescan",
+            Action::CycleInterface => "cycle_interface",
+            Action::Connect => "connect",
+            Action::Submit => "submit",
+            Action::TogglePassword => "toggle_password",
+            Action::Select => "select",
+            Action::Cancel => "cancel",
+            Action::Confirm => "confirm",
+            Action::Help => "help",
+            Action::PageDown => "page_down",
+            Action::PageUp => "page_up",
+            Action::Search => "search",
+            Action::NextMatch => "next_match",
+            Action::PrevMatch => "prev_match",
+            Action::FilterOpen => "filter_open",
+            Action::FilterSaved => "filter_saved",
+            Action::Filter5Ghz => "filter_5ghz",
+            Action::FilterSignal => "filter_signal",
+            Action::ClearFilter => "clear_filter",
+            Action::CycleSort => "cycle_sort",
+            Action::ReverseSort => "reverse_sort",
+            Action::Details => "details",
+            Action::Channels => "channels",
+            Action::Waypoint => "waypoint",
+            Action::History => "history",
+        }
+    }
+
+    /// Short description for the status bar.
+    pub fn label(self, mode: Mode) -> &'static str {
+        match (self, mode) {
+            (Action::Submit, Mode::Search) => return "Keep",
+            (Action::Cancel, Mode::Search) => return "Clear",
+            (Action::Next, Mode::Channels) => return "Next band",
+            (Action::Prev, Mode::Channels) => return "Prev band",
+            (Action::Cancel, Mode::Channels | Mode::History) => return "Back",
+            (Action::Submit, Mode::Waypoint) => return "Drop",
+            _ => {}
+        }
+        match self {
+            Action::Quit => "Quit",
+            Action::Next => "Down",
+            Action::Prev => "Up",
+            Action::Rescan => "Rescan",
+            Action::CycleInterface => "Switch IF",
+            Action::Connect | Action::Submit => "Connect",
+            Action::TogglePassword => "Show/Hide",
+            Action::Select => "Select",
+            Action::Cancel => "Cancel",
+            Action::Confirm => "Yes",
+            Action::Help => "Help",
+            Action::PageDown => "Page down",
+            Action::PageUp => "Page up",
+            Action::Search => "Search",
+            Action::NextMatch => "Next match",
+            Action::PrevMatch => "Prev match",
+            Action::FilterOpen => "Open only",
+            Action::FilterSaved => "Saved only",
+            Action::Filter5Ghz => "5 GHz only",
+            Action::FilterSignal => "Min signal",
+            Action::ClearFilter => "Clear filter",
+            Action::CycleSort => "Sort",
+            Action::ReverseSort => "Reverse",
+            Action::Details => "Details",
+            Action::Channels => "Channels",
+            Action::Waypoint => "Waypoint",
+            Action::History => "History",
+        }
+    }
+
+    /// Longer description for the help overlay.
+    pub fn description(self, mode: Mode) -> &'static str {
+        match (self, mode) {
+            (Action::Submit, Mode::Search) => return "Keep the search and go back to the list",
+            (Action::Cancel, Mode::Search) => return "Clear the search",
+            (Action::Next, Mode::Channels) => return "Show the next band",
+            (Action::Prev, Mode::Channels) => return "Show the previous band",
+            (Action::Rescan, Mode::Channels) => return "Rescan and redraw the channels",
+            (Action::Cancel, Mode::Channels | Mode::History) => return "Back to the network list",
+            (Action::Submit, Mode::Waypoint) => return "Log the waypoint and tag what follows with it",
+            _ => {}
+        }
+        match self {
+            Action::Quit => "Quit",
+            Action::Next => "Move down",
+            Action::Prev => "Move up",
+            Action::Rescan => "Rescan for networks",
+            Action::CycleInterface => "Switch to the next Wi-Fi device",
+            Action::Connect => "Connect; actions for the active network",
+            Action::Submit => "Connect with this password",
+            Action::TogglePassword => "Show or hide the password",
+            Action::Select => "Run the selected action",
+            Action::Cancel => "Close",
+            Action::Confirm => "Yes (any other key is no)",
+            Action::Help => "Show this help",
+            Action::PageDown => "Scroll down a page",
+            Action::PageUp => "Scroll up a page",
+            Action::Search => "Search SSIDs",
+            Action::NextMatch => "Next match",
+            Action::PrevMatch => "Previous match",
+            Action::FilterOpen => "Show only open networks",
+            Action::FilterSaved => "Show only saved networks",
+            Action::Filter5Ghz => "Show only 5 GHz networks",
+            Action::FilterSignal => "Raise the minimum signal, then back to none",
+            Action::ClearFilter => "Clear the search and all filters",
+            Action::CycleSort => "Sort by the next key",
+            Action::ReverseSort => "Reverse the sort order",
+            Action::Details => "Show or hide the signal history of the selected network",
+            Action::Channels => "Show how crowded each channel is",
+            Action::Waypoint => "Drop a named waypoint into the survey",
+            Action::History => "Show access points seen in earlier scans",
+        }
+    }
+}
+
+/// Every action that exists in a mode, with its default keys. The order is
+/// the order keys are listed in help texts.
+const DEFAULTS: &[(Mode, Action, &[&str])] = &[
+    (Mode::Browse, Action::Next, &["Down", "j"]),
+    (Mode::Browse, Action::Prev, &["Up", "k"]),
+    (Mode::Browse, Action::CycleInterface, &["i"]),
+    (Mode::Browse, Action::Rescan, &["r"]),
+    (Mode::Browse, Action::Connect, &["Enter"]),
+    (Mode::Browse, Action::Quit, &["q", "Esc"]),
+    (Mode::Browse, Action::Search, &["/"]),
+    (Mode::Browse, Action::NextMatch, &["n"]),
+    (Mode::Browse, Action::PrevMatch, &["N"]),
+    (Mode::Browse, Action::FilterOpen, &["o"]),
+    (Mode::Browse, Action::FilterSaved, &["s"]),
+    (Mode::Browse, Action::Filter5Ghz, &["5"]),
+    (Mode::Browse, Action::FilterSignal, &["m"]),
+    (Mode::Browse, Action::ClearFilter, &["x"]),
+    (Mode::Browse, Action::CycleSort, &["S"]),
+    (Mode::Browse, Action::ReverseSort, &["R"]),
+    (Mode::Browse, Action::Details, &["d"]),
+    (Mode::Browse, Action::Channels, &["c"]),
+    (Mode::Browse, Action::Waypoint, &["w"]),
+    (Mode::Browse, Action::History, &["H"]),
+    (Mode::Browse, Action::Help, &["?"]),
+    (Mode::Password, Action::Submit, &["Enter"]),
+    (Mode::Password, Action::TogglePassword, &["Tab"]),
+    (Mode::Password, Action::Cancel, &["Esc"]),
+    (Mode::ActionMenu, Action::Next, &["Down", "j"]),
+    (Mode::ActionMenu, Action::Prev, &["Up", "k"]),
+    (Mode::ActionMenu, Action::Select, &["Enter"]),
+    (Mode::ActionMenu, Action::Cancel, &["Esc"]),
+    (Mode::ActionMenu, Action::Help, &["?"]),
+    (Mode::Confirm, Action::Confirm, &["y", "Y"]),
+    (Mode::Help, Action::Next, &["Down", "j"]),
+    (Mode::Help, Action::Prev, &["Up", "k"]),
+    (Mode::Help, Action::PageDown, &["PgDn", "Space"]),
+    (Mode::Help, Action::PageUp, &["PgUp"]),
+    (Mode::Help, Action::Cancel, &["Esc", "q", "?"]),
+    (Mode::Search, Action::Submit, &["Enter"]),
+    (Mode::Search, Action::Next, &["Down"]),
+    (Mode::Search, Action::Prev, &["Up"]),
+    (Mode::Search, Action::Cancel, &["Esc"]),
+    (Mode::Channels, Action::Next, &["Right", "l", "Tab"]),
+    (Mode::Channels, Action::Prev, &["Left", "h"]),
+    (Mode::Channels, Action::Rescan, &["r"]),
+    (Mode::Channels, Action::Cancel, &["Esc", "q", "c"]),
+    (Mode::Channels, Action::Help, &["?"]),
+    (Mode::Waypoint, Action::Submit, &["Enter"]),
+    (Mode::Waypoint, Action::Cancel, &["Esc"]),
+    (Mode::History, Action::Next, &["Down", "j"]),
+    (Mode::History, Action::Prev, &["Up", "k"]),
+    (Mode::History, Action::PageDown, &["PgDn", "Space"]),
+    (Mode::History, Action::PageUp, &["PgUp"]),
+    (Mode::History, Action::Cancel, &["Esc", "q", "H"]),
+    (Mode::History, Action::Help, &["?"]),
+];
+
+/// `[keys]` section of the config file: mode -> action -> keys.
+pub type KeysConfig = BTreeMap<Mode, BTreeMap<Action, Vec<String>>>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// Parses names like `q`, `Enter`, `Ctrl-w`, `Alt-Backspace` or `F5`.
+    fn parse(spec: &str) -> Result<Self, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = spec;
+        // A lone "-" is a key, not a modifier separator
+        while let Some((prefix, tail)) = rest.split_once('-').filter(|(_, tail)| !tail.is_empty()) {
+            match prefix.to_lowercase().as_str() {
+                "ctrl" => modifiers |= KeyModifiers::CONTROL,
+                "alt" => modifiers |= KeyModifiers::ALT,
+                "shift" => modifiers |= KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier in key '{}'", spec)),
+            }
+            rest = tail;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" | "pgup" => KeyCode::PageUp,
+                "pagedown" | "pgdn" => KeyCode::PageDown,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "space" => KeyCode::Char(' '),
+                name => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n @ 1..=24) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key '{}'", spec)),
+                },
+            },
+        };
+        Ok(Self::normalized(code, modifiers))
+    }
+
+    fn from_event(event: &KeyEvent) -> Self {
+        Self::normalized(event.code, event.modifiers)
+    }
+
+    // Shift is already part of a typed character and of BackTab, so
+    // "Shift-a" is the same key as "A"
+    fn normalized(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                let mut upper = c.to_uppercase();
+                match (upper.next(), upper.next()) {
+                    (Some(upper), None) => KeyCode::Char(upper),
+                    _ => code,
+                }
+            }
+            _ => code,
+        };
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        Self {
+            code,
+            modifiers: modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT),
+        }
+    }
+
+    /// Plain characters are text in the password prompt and the search field.
+    fn is_text(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_)) && (self.modifiers - KeyModifiers::SHIFT).is_empty()
+    }
+
+    pub fn name(&self) -> String {
+        let mut name = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            name.push_str("Ctrl-");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            name.push_str("Alt-");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            name.push_str("Shift-");
+        }
+        match self.code {
+            KeyCode::Char(' ') => name.push_str("Space"),
+            KeyCode::Char(c) => name.push(c),
+            KeyCode::BackTab => name.push_str("Shift-Tab"),
+            KeyCode::PageUp => name.push_str("PgUp"),
+            KeyCode::PageDown => name.push_str("PgDn"),
+            KeyCode::F(n) => name.push_str(&format!("F{}", n)),
+            other => name.push_str(&format!("{:?}", other)),
+        }
+        name
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Keymap {
+    /// In `DEFAULTS` order, so help texts stay stable
+    bindings: Vec<(Mode, Action, Vec<Key>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new(&KeysConfig::new()).expect("default key bindings are valid")
+    }
+}
+
+impl Keymap {
+    /// Applies the overrides from the config file on top of the defaults and
+    /// rejects keys bound twice in the same mode.
+    pub fn new(overrides: &KeysConfig) -> Result<Self, String> {
+        for (mode, actions) in overrides {
+            for action in actions.keys() {
+                if !DEFAULTS.iter().any(|(m, a, _)| m == mode && a == action) {
+                    return Err(format!("'{}' is not an action in [keys.{}]", action.name(), mode.name()));
+                }
+            }
+        }
+
+        let mut bindings: Vec<(Mode, Action, Vec<Key>)> = Vec::with_capacity(DEFAULTS.len());
+        for &(mode, action, default_keys) in DEFAULTS {
+            let specs: Vec<&str> = match overrides.get(&mode).and_then(|m| m.get(&action)) {
+                Some(keys) => keys.iter().map(String::as_str).collect(),
+                None => default_keys.to_vec(),
+            };
+            let mut keys = Vec::with_capacity(specs.len());
+            for spec in specs {
+                let key = Key::parse(spec).map_err(|e| format!("{} in [keys.{}]", e, mode.name()))?;
+                if mode.takes_text() && key.is_text() {
+                    return Err(format!(
+                        "'{}' would be typed into the text field; bind {} in [keys.{}] to a special or Ctrl key",
+                        spec,
+                        action.name(),
+                        mode.name()
+                    ));
+                }
+                let taken = bindings.iter().find(|(m, _, keys)| *m == mode && keys.contains(&key));
+                if let Some((_, other, _)) = taken {
+                    return Err(format!(
+                        "'{}' is bound to both {} and {} in [keys.{}]",
+                        spec,
+                        other.name(),
+                        action.name(),
+                        mode.name()
+                    ));
+                }
+                keys.push(key);
+            }
+            bindings.push((mode, action, keys));
+        }
+        Ok(Self { bindings })
+    }
+
+    pub fn action(&self, mode: Mode, event: &KeyEvent) -> Option<Action> {
+        let key = Key::from_event(event);
+        self.bindings
+            .iter()
+            .find(|(m, _, keys)| *m == mode && keys.contains(&key))
+            .map(|(_, action, _)| *action)
+    }
+
+    /// Name of the first key bound to `action`, if any.
+    pub fn key(&self, mode: Mode, action: Action) -> Option<String> {
+        self.bindings
+            .iter()
+            .find(|(m, a, _)| *m == mode && *a == action)
+            .and_then(|(_, _, keys)| keys.first())
+            .map(Key::name)
+    }
+
+    /// Every action of `mode` with the names of its keys, in help order.
+    pub fn bindings(&self, mode: Mode) -> Vec<(Action, Vec<String>)> {
+        self.bindings
+            .iter()
+            .filter(|(m, _, _)| *m == mode)
+            .map(|(_, action, keys)| (*action, keys.iter().map(Key::name).collect()))
+            .collect()
+    }
+
+    /// "r: Rescan | q: Quit" for the given actions, skipping unbound ones.
+    pub fn hint(&self, mode: Mode, actions: &[Action]) -> String {
+        self.hint_within(mode, actions, usize::MAX)
+    }
+
+    /// Like `hint`, but stops before the first action that would make it
+    /// wider than `width` columns.
+    pub fn hint_within(&self, mode: Mode, actions: &[Action], width: usize) -> String {
+        let mut hint = String::new();
+        for &action in actions {
+            let Some(key) = self.key(mode, action) else { continue };
+            let part = format!("{}: {}", key, action.label(mode));
+            let next = if hint.is_empty() { part } else { format!("{} | {}", hint, part) };
+            if next.width() > width {
+                break;
+            }
+            hint = next;
+        }
+        hint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        Key { code, modifiers }
+    }
+
+    fn overrides(mode: Mode, bindings: &[(Action, &[&str])]) -> KeysConfig {
+        let actions = bindings
+            .iter()
+            .map(|(action, keys)| (*action, keys.iter().map(|k| k.to_string()).collect()))
+            .collect();
+        KeysConfig::from([(mode, actions)])
+    }
+
+    #[test]
+    fn parses_key_names() {
+        let none = KeyModifiers::NONE;
+        let cases = [
+            ("q", key(KeyCode::Char('q'), none)),
+            ("-", key(KeyCode::Char('-'), none)),
+            ("Enter", key(KeyCode::Enter, none)),
+            ("esc", key(KeyCode::Esc, none)),
+            ("Space", key(KeyCode::Char(' '), none)),
+            ("PgDn", key(KeyCode::PageDown, none)),
+            ("F5", key(KeyCode::F(5), none)),
+            ("Ctrl-r", key(KeyCode::Char('r'), KeyModifiers::CONTROL)),
+            ("ctrl-alt-x", key(KeyCode::Char('x'), KeyModifiers::CONTROL | KeyModifiers::ALT)),
+            ("Alt-Backspace", key(KeyCode::Backspace, KeyModifiers::ALT)),
+            ("Ctrl--", key(KeyCode::Char('-'), KeyModifiers::CONTROL)),
+            ("Shift-Tab", key(KeyCode::BackTab, none)),
+            ("Shift-Up", key(KeyCode::Up, KeyModifiers::SHIFT)),
+            ("Shift-a", key(KeyCode::Char('A'), none)),
+            ("Ctrl-Shift-a", key(KeyCode::Char('A'), KeyModifiers::CONTROL)),
+            ("Shift-ä", key(KeyCode::Char('Ä'), none)),
+        ];
+        for (spec, expected) in cases {
+            assert_eq!(Key::parse(spec), Ok(expected), "{spec}");
+        }
+        for spec in ["", "Hyper-a", "F25", "Enterr", "Ctrl-"] {
+            assert!(Key::parse(spec).is_err(), "{spec}");
+        }
+    }
+
+    #[test]
+    fn shifted_letters_match_typed_capitals() {
+        let typed = KeyEvent::new(KeyCode::Char('A'), KeyModifiers::SHIFT);
+        assert_eq!(Key::from_event(&typed), Key::parse("Shift-a").unwrap());
+        assert_eq!(Key::from_event(&typed), Key::parse("A").unwrap());
+        assert_ne!(Key::parse("Shift-a"), Key::parse("a"));
+    }
+
+    #[test]
+    fn rejects_conflicting_bindings() {
+        let keymap = Keymap::new(&overrides(Mode::Browse, &[(Action::Rescan, &["Shift-s"])]));
+        // Shift-s is the default key for cycling the sort, S
+        let error = keymap.unwrap_err();
+        assert!(error.contains("bound to both rescan and cycle_sort"), "{error}");
+
+        let keymap = Keymap::new(&overrides(Mode::Browse, &[(Action::Rescan, &["q"])]));
+        assert!(keymap.unwrap_err().contains("bound to both"));
+
+        let keymap = Keymap::new(&overrides(Mode::Browse, &[(Action::Rescan, &["F5", "Ctrl-r"])])).unwrap();
+        let event = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.action(Mode::Browse, &event), Some(Action::Rescan));
+        // The default 'r' is replaced, not added to
+        let event = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::NONE);
+        assert_eq!(keymap.action(Mode::Browse, &event), None);
+    }
+
+    #[test]
+    fn hints_are_measured_in_columns() {
+        let keymap = Keymap::new(&overrides(Mode::Browse, &[(Action::Rescan, &["日"])])).unwrap();
+        let label = Action::Rescan.label(Mode::Browse);
+        let hint = format!("日: {}", label);
+        assert_eq!(keymap.hint_within(Mode::Browse, &[Action::Rescan], hint.width()), hint);
+        // One column short, although it has as many chars as that
+        assert_eq!(keymap.hint_within(Mode::Browse, &[Action::Rescan], hint.width() - 1), "");
+    }
+
+    #[test]
+    fn rejects_text_keys_in_text_fields_and_unknown_actions() {
+        let keymap = Keymap::new(&overrides(Mode::Password, &[(Action::Cancel, &["x"])]));
+        assert!(keymap.unwrap_err().contains("would be typed into the text field"));
+        let keymap = Keymap::new(&overrides(Mode::Password, &[(Action::Rescan, &["F5"])]));
+        assert!(keymap.unwrap_err().contains("is not an action in [keys.password]"));
+        assert!(Keymap::new(&overrides(Mode::Password, &[(Action::Cancel, &["Ctrl-g"])])).is_ok());
+    }
+}
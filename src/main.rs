@@ -1,28 +1,159 @@
-use std::{env, io};
-use std::process::Command;
-use std::time::Duration;
+mod agent;
+mod bar;
+mod channels;
+mod config;
+mod daemon;
+mod dmenu;
+mod error;
+mod filter;
+mod history;
+mod hooks;
+mod input;
+mod keymap;
+mod notify;
+mod output;
+mod ssid;
+mod survey;
+mod theme;
+mod trend;
+
+use std::{env, fs, io};
+use std::io::{Read, Write};
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use error::{CommandFailure, ConnectError, Error};
+use keymap::{Action, Mode};
+use output::{ActionReport, DeviceStatus, HistoryEntry, HistoryReport, NetworkList, OutputFormat, StatusReport};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use ssid::Ssid;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event, KeyCode,
+        KeyEventKind, MouseButton, MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Clear},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Clear, Sparkline},
 };
 
-const MAX_SSID_DISPLAY_LEN: usize = 25;
-
 // --- Data Structures ---
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 struct Network {
-    ssid: String,
+    ssid: Ssid,
     bssid: String,
     security: String,
     signal: u8,
     in_use: bool,
+    /// MHz; 0 if nmcli didn't say
+    #[serde(default)]
+    freq: u32,
+    #[serde(default)]
+    chan: u32,
+    /// Channel width in MHz; 0 if unknown
+    #[serde(skip)]
+    bandwidth: u32,
+    /// A connection profile with this name exists
+    #[serde(default)]
+    saved: bool,
+    /// Seconds since NetworkManager last saw the access point, if known
+    #[serde(default)]
+    last_seen: Option<u64>,
+    /// NetworkManager's D-Bus object for the access point
+    #[serde(skip)]
+    ap_path: String,
+}
+
+/// What a survey scan on the worker thread found.
+struct SurveyScan {
+    interface: String,
+    access_points: Vec<Network>,
+    scan: Scan,
+}
+
+/// One scan of an interface: every access point, for the per-BSSID signal
+/// trends, and one entry per network for the list.
+#[derive(Default)]
+struct Scan {
+    access_points: Vec<Network>,
+    networks: Vec<Network>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Band {
+    Ghz2,
+    Ghz5,
+    Ghz6,
+    Unknown,
+}
+
+impl Band {
+    fn of(freq: u32) -> Self {
+        match freq {
+            2400..=2500 => Band::Ghz2,
+            4900..=5900 => Band::Ghz5,
+            5925..=7125 => Band::Ghz6,
+            _ => Band::Unknown,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Band::Ghz2 => "2.4 GHz",
+            Band::Ghz5 => "5 GHz",
+            Band::Ghz6 => "6 GHz",
+            Band::Unknown => "unknown band",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum SecurityFilter {
+    Open,
+    Wep,
+    Wpa,
+    Wpa2,
+    Wpa3,
+    Enterprise,
+}
+
+impl SecurityFilter {
+    fn parse(value: &str) -> Result<Self, Error> {
+        match value {
+            "open" => Ok(SecurityFilter::Open),
+            "wep" => Ok(SecurityFilter::Wep),
+            "wpa" => Ok(SecurityFilter::Wpa),
+            "wpa2" => Ok(SecurityFilter::Wpa2),
+            "wpa3" => Ok(SecurityFilter::Wpa3),
+            "enterprise" => Ok(SecurityFilter::Enterprise),
+            _ => Err(Error::Usage(format!(
+                "Unknown security '{}', expected open, wep, wpa, wpa2, wpa3 or enterprise",
+                value
+            ))),
+        }
+    }
+
+    fn matches(self, security: &str) -> bool {
+        match self {
+            SecurityFilter::Open => security.is_empty() || security == "--",
+            SecurityFilter::Wep => security.contains("WEP"),
+            SecurityFilter::Wpa => security.contains("WPA") || security.contains("RSN"),
+            SecurityFilter::Wpa2 => security.contains("WPA2"),
+            SecurityFilter::Wpa3 => security.contains("WPA3"),
+            SecurityFilter::Enterprise => security.contains("802.1X"),
+        }
+    }
 }
 
 #[derive(PartialEq)]
@@ -31,15 +162,43 @@ enum AppMode {
     Browsing,
     PasswordInput,
     ActionMenu, // For connected/saved networks (Disconnect, Forget)
+    ConfirmForget,
     Processing(String),
     Message(String), // Press any key to dismiss
+    Help(Mode), // Opened from the given mode and returns to it
+    Search,
+    Channels,
+    Waypoint,
+    History,
+}
+
+impl AppMode {
+    /// Which key bindings apply; `None` where keys aren't mapped to actions.
+    fn keymap_mode(&self) -> Option<Mode> {
+        match self {
+            AppMode::Browsing => Some(Mode::Browse),
+            AppMode::PasswordInput => Some(Mode::Password),
+            AppMode::ActionMenu => Some(Mode::ActionMenu),
+            AppMode::ConfirmForget => Some(Mode::Confirm),
+            AppMode::Help(_) => Some(Mode::Help),
+            AppMode::Search => Some(Mode::Search),
+            AppMode::Channels => Some(Mode::Channels),
+            AppMode::Waypoint => Some(Mode::Waypoint),
+            AppMode::History => Some(Mode::History),
+            AppMode::Scanning | AppMode::Processing(_) | AppMode::Message(_) => None,
+        }
+    }
 }
 
 struct App {
     mode: AppMode,
+    // Everything from the last scan; `networks` is what the filter lets through
+    scanned: Vec<Network>,
     networks: Vec<Network>,
     list_state: ListState,
-    input_buffer: String,
+    input_buffer: input::LineEditor,
+    // Error from the last attempt, shown until the password is edited
+    input_error: Option<String>,
     show_password: bool,
     wifi_interfaces: Vec<String>,
     current_interface: String,
@@ -49,27 +208,177 @@ struct App {
     action_state: ListState,
     
     // Target for connection
-    target_ssid: String,
+    target_ssid: Ssid,
     target_bssid: String,
     target_security: String,
+
+    // Set while the password prompt is answering a NetworkManager secret request
+    pending_secret: Option<agent::SecretRequest>,
+
+    help_scroll: u16,
+
+    sort: config::SortKey,
+    sort_reverse: bool,
+    filter: filter::Filter,
+    search_input: input::LineEditor,
+
+    // Time and row of the last click, to spot double-clicks
+    last_click: Option<(Instant, usize)>,
+
+    trends: trend::Trends,
+    show_details: bool,
+
+    // Every BSSID, not one per SSID, for the channel view
+    access_points: Vec<Network>,
+    channel_band: Band,
+
+    // Set while recording with --survey
+    survey: Option<survey::Survey>,
+    // Result of the survey scan running in the background, if one is
+    survey_scan: Option<Receiver<SurveyScan>>,
+    waypoint_input: input::LineEditor,
+
+    history: Vec<history::Sighting>,
+    history_state: ListState,
 }
 
 impl App {
     fn new(wifi_interfaces: Vec<String>, current_interface: String) -> Self {
         Self {
             mode: AppMode::Scanning,
+            scanned: Vec::new(),
             networks: Vec::new(),
             list_state: ListState::default(),
-            input_buffer: String::new(),
+            input_buffer: input::LineEditor::default(),
+            input_error: None,
             show_password: false,
             wifi_interfaces,
             current_interface,
             action_items: vec!["Disconnect", "Forget", "Cancel"],
             action_state: ListState::default(),
-            target_ssid: String::new(),
+            target_ssid: Ssid::default(),
             target_bssid: String::new(),
             target_security: String::new(),
+            pending_secret: None,
+            help_scroll: 0,
+            sort: config::get().behavior.sort,
+            sort_reverse: config::get().behavior.sort_reverse,
+            filter: filter::Filter::default(),
+            search_input: input::LineEditor::default(),
+            last_click: None,
+            trends: trend::Trends::default(),
+            show_details: false,
+            access_points: Vec::new(),
+            channel_band: Band::Ghz2,
+            survey: None,
+            survey_scan: None,
+            waypoint_input: input::LineEditor::default(),
+            history: Vec::new(),
+            history_state: ListState::default(),
+        }
+    }
+
+    /// Replaces the scan results, keeping the cursor on the same network.
+    fn set_networks(&mut self, scan: Scan) {
+        // An empty scan is as likely a failed one as a disconnect
+        if !scan.access_points.is_empty() {
+            hooks::observe(&self.current_interface, scan.access_points.iter().find(|ap| ap.in_use));
+        }
+        self.trends.record(&scan.access_points);
+        self.scanned = scan.networks;
+        self.apply_filter();
+    }
+
+    /// Opens the channel view on the band of the connected network.
+    fn open_channels(&mut self) {
+        self.load_access_points();
+        self.channel_band = self
+            .access_points
+            .iter()
+            .find(|ap| ap.in_use)
+            .map(|ap| Band::of(ap.freq))
+            .filter(|band| channels::BANDS.contains(band))
+            .unwrap_or(Band::Ghz2);
+        self.mode = AppMode::Channels;
+    }
+
+    fn load_access_points(&mut self) {
+        self.access_points = scan_access_points(&self.current_interface).unwrap_or_default();
+    }
+
+    /// Asks for a fresh scan in the background, since nmcli waits for the
+    /// radio to finish it. `finish_survey_scan` logs the result.
+    fn start_survey_scan(&mut self) {
+        let (sender, receiver) = mpsc::channel();
+        let interface = self.current_interface.clone();
+        thread::spawn(move || {
+            // Rescans that come too quickly are turned down; the results of
+            // the last one are still there to log
+            let _ = rescan_interface(&interface);
+            let access_points = scan_access_points(&interface).unwrap_or_default();
+            let scan = get_networks(&interface);
+            let _ = sender.send(SurveyScan { interface, access_points, scan });
+        });
+        self.survey_scan = Some(receiver);
+    }
+
+    /// Logs the survey scan once it is done. Only a failed write is an
+    /// error; a scan that fails is logged as empty.
+    fn finish_survey_scan(&mut self) -> Result<(), Error> {
+        let Some(receiver) = &self.survey_scan else { return Ok(()) };
+        let done = match receiver.try_recv() {
+            Ok(done) => done,
+            Err(TryRecvError::Empty) => return Ok(()),
+            Err(TryRecvError::Disconnected) => {
+                self.survey_scan = None;
+                return Ok(());
+            }
+        };
+        self.survey_scan = None;
+        let Some(survey) = self.survey.as_mut() else { return Ok(()) };
+        survey.record_scan(&done.interface, &done.access_points)?;
+        // The interface may have been switched in the meantime
+        if done.interface == self.current_interface {
+            self.access_points = done.access_points;
+            if self.mode == AppMode::Browsing {
+                self.set_networks(done.scan);
+            }
         }
+        Ok(())
+    }
+
+    /// Steps through the bands of the channel view, `by` at a time.
+    fn cycle_band(&mut self, by: usize) {
+        let bands = channels::BANDS;
+        let current = bands.iter().position(|&b| b == self.channel_band).unwrap_or(0);
+        self.channel_band = bands[(current + by) % bands.len()];
+    }
+
+    /// Opens the history view. Scanning first puts every access point in
+    /// range on record and tells which of the others are gone.
+    fn open_history(&mut self) -> Result<(), Error> {
+        self.load_access_points();
+        history::flush()?;
+        self.history = history::load()?;
+        self.history_state.select(if self.history.is_empty() { None } else { Some(0) });
+        self.mode = AppMode::History;
+        Ok(())
+    }
+
+    /// Moves the history selection by `by` rows, stopping at either end.
+    fn move_history(&mut self, by: isize) {
+        let Some(last) = self.history.len().checked_sub(1) else { return };
+        let current = self.history_state.selected().unwrap_or(0);
+        self.history_state.select(Some(current.saturating_add_signed(by).min(last)));
+    }
+
+    /// Rebuilds `networks` from the scan with the current filter and sort order.
+    fn apply_filter(&mut self) {
+        let selected = self.list_state.selected().and_then(|i| self.networks.get(i)).map(|n| n.ssid.clone());
+        self.networks = self.scanned.iter().filter(|n| self.filter.matches(n)).cloned().collect();
+        sort_networks(&mut self.networks, self.sort, self.sort_reverse);
+        let index = selected.and_then(|ssid| self.networks.iter().position(|n| n.ssid == ssid));
+        self.list_state.select(index.or(if self.networks.is_empty() { None } else { Some(0) }));
     }
 
     fn next_network(&mut self) {
@@ -130,24 +439,57 @@ impl App {
         self.current_interface = self.wifi_interfaces[next_idx].clone();
         self.list_state.select(None);
     }
+
+    fn begin_secret_prompt(&mut self, request: agent::SecretRequest) {
+        self.target_ssid = request.ssid.clone();
+        self.target_bssid.clear();
+        self.target_security = request.security.clone();
+        self.input_buffer.clear();
+        self.input_error = None;
+        self.show_password = false;
+        self.pending_secret = Some(request);
+        self.mode = AppMode::PasswordInput;
+    }
 }
 
 // --- Helper Functions ---
 
-fn run_command(cmd: &str, args: &[&str]) -> Result<String, String> {
+fn run_command(cmd: &str, args: &[&str]) -> Result<String, Error> {
     let output = Command::new(cmd)
         .args(args)
         .output()
-        .map_err(|e| e.to_string())?;
-    
+        .map_err(|source| Error::Spawn { command: cmd.to_string(), source })?;
+    command_result(cmd, args, output)
+}
+
+fn command_result(cmd: &str, args: &[&str], output: Output) -> Result<String, Error> {
     let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
     let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
 
     if output.status.success() {
         Ok(stdout)
     } else {
-        Err(if !stderr.is_empty() { stderr } else { stdout })
+        let message = if !stderr.is_empty() { stderr } else { stdout };
+        Err(Error::Command(CommandFailure::new(cmd, args, output.status.code(), message)))
+    }
+}
+
+/// Like `run_command`, but writes `input` to the command's stdin.
+fn run_command_with_input(cmd: &str, args: &[&str], input: &str) -> Result<String, Error> {
+    let spawn_error = |source| Error::Spawn { command: cmd.to_string(), source };
+    let mut child = Command::new(cmd)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(spawn_error)?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // nmcli may exit before reading it, e.g. if the network is gone
+        let _ = stdin.write_all(input.as_bytes());
     }
+    let output = child.wait_with_output().map_err(spawn_error)?;
+    command_result(cmd, args, output)
 }
 
 #[derive(Default)]
@@ -155,59 +497,270 @@ struct CliOptions {
     rescan: bool,
     disconnect: bool,
     status: bool,
+    list: bool,
+    connect: Option<Ssid>,
+    forget: Option<Ssid>,
+    /// `--connect`/`--forget` were given the SSID as hex
+    hex: bool,
+    bssid: Option<String>,
+    password_file: Option<String>,
+    ask_password: bool,
+    wait: Option<u64>,
+    dmenu: Option<String>,
+    dmenu_password_flag: Option<String>,
+    bar: bool,
+    watch: bool,
+    bar_format: bar::BarFormat,
+    min_signal: Option<u8>,
+    security: Option<SecurityFilter>,
+    agent: bool,
+    secret_agent: bool,
+    daemon: bool,
+    no_daemon: bool,
+    notify: bool,
     interface: Option<String>,
+    format: OutputFormat,
+    config: Option<String>,
+    theme: Option<config::Theme>,
+    icons: Option<config::IconSet>,
+    survey: Option<String>,
+    survey_interval: Option<u64>,
+    history: bool,
 }
 
 fn print_usage() {
     println!("wifi_menu - TUI Wi-Fi manager");
     println!();
     println!("Usage:");
-    println!("  wifi_menu [--interface <ifname>]");
+    println!("  wifi_menu [--interface <ifname>] [--agent] [--notify] [--theme <name>] [--icons <set>]");
+    println!("            [--survey <file> [--survey-interval <secs>]]");
     println!("  wifi_menu --rescan [--interface <ifname>]");
     println!("  wifi_menu --disconnect [--interface <ifname>]");
     println!("  wifi_menu --status [--interface <ifname>]");
+    println!("  wifi_menu --list [--interface <ifname>] [--rescan] [--min-signal <0-100>]");
+    println!("                   [--security open|wep|wpa|wpa2|wpa3|enterprise]");
+    println!("  wifi_menu --connect <ssid> [--hex] [--bssid <bssid>] [--password-file <file> | --ask-password]");
+    println!("                      [--wait <secs>] [--interface <ifname>]");
+    println!("  wifi_menu --forget <ssid> [--hex]");
+    println!("  wifi_menu --history [--bssid <bssid>]");
+    println!("  wifi_menu --dmenu <command> [--dmenu-password-flag <flag>] [--interface <ifname>]");
+    println!("  wifi_menu --bar [--watch] [--notify] [--format waybar|i3blocks|polybar]");
+    println!("                  [--interface <ifname>]");
+    println!("  wifi_menu --secret-agent");
+    println!("  wifi_menu --daemon [--notify]");
+    println!("  wifi_menu --print-default-config");
     println!("  wifi_menu --help");
+    println!();
+    println!("  --password-file  Read the password from the first line of a file ('-' for stdin)");
+    println!("  --ask-password   Prompt for the password on the terminal");
+    println!("  --hex            The SSID for --connect/--forget is hex bytes, e.g. 436166E9");
+    println!("  --wait <secs>    After connecting, wait until the device has an IPv4 address");
+    println!("  --history        Access points seen in earlier scans, most recent first");
+    println!("  --dmenu <cmd>    Use a launcher such as 'rofi -dmenu' or 'fuzzel --dmenu' as the menu");
+    println!("  --config <file>  Read settings from this file instead of ~/.config/wifi_menu/config.toml");
+    println!("  --format <fmt>   Output format for non-interactive commands: text, tsv, json");
+    println!("  --json           Same as --format json");
+    println!("  --agent          Answer NetworkManager secret requests while the TUI runs");
+    println!("  --secret-agent   Run only the secret agent, prompting when a request arrives");
+    println!("  --daemon         Cache state and serve it to other invocations over a socket");
+    println!("  --no-daemon      Run nmcli directly even if a daemon is running");
+    println!("  --notify         Show desktop notifications for connection events");
+    println!("  --theme <name>   Colors: dark, light, high-contrast, monochrome");
+    println!("  --icons <set>    Icons for the TUI, --dmenu and --bar: nerd, unicode, ascii");
+    println!("  --survey <file>  Record a site survey to a CSV (*.csv) or JSON Lines file");
+    println!("  --survey-interval <secs>");
+    println!("                   Seconds between survey scans instead of survey.interval");
 }
 
-fn parse_cli_options() -> Result<CliOptions, String> {
+fn parse_cli_options() -> Result<CliOptions, Error> {
     let mut opts = CliOptions::default();
     let mut args = env::args().skip(1);
+    let mut format_arg = None;
+    let mut ssid_arg = None;
 
     while let Some(arg) = args.next() {
         match arg.as_str() {
             "--rescan" => opts.rescan = true,
             "--disconnect" => opts.disconnect = true,
             "--status" => opts.status = true,
+            "--list" => opts.list = true,
+            "--history" => opts.history = true,
+            "--min-signal" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| Error::Usage("--min-signal requires a value".to_string()))?;
+                opts.min_signal = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|n| *n <= 100)
+                        .ok_or_else(|| Error::Usage(format!("Invalid --min-signal '{}'", value)))?,
+                );
+            }
+            "--connect" | "--forget" | "--bssid" | "--password-file" | "--dmenu"
+            | "--dmenu-password-flag" | "--config" | "--survey" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| Error::Usage(format!("{} requires a value", arg)))?;
+                match arg.as_str() {
+                    "--connect" | "--forget" => ssid_arg = Some((arg.clone(), value)),
+                    "--bssid" => opts.bssid = Some(value),
+                    "--dmenu" => opts.dmenu = Some(value),
+                    "--dmenu-password-flag" => opts.dmenu_password_flag = Some(value),
+                    "--config" => opts.config = Some(value),
+                    "--survey" => opts.survey = Some(value),
+                    _ => opts.password_file = Some(value),
+                }
+            }
+            "--ask-password" => opts.ask_password = true,
+            "--hex" => opts.hex = true,
+            "--wait" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| Error::Usage("--wait requires a value".to_string()))?;
+                opts.wait = Some(
+                    value
+                        .parse()
+                        .map_err(|_| Error::Usage(format!("Invalid --wait '{}'", value)))?,
+                );
+            }
+            "--survey-interval" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| Error::Usage("--survey-interval requires a value".to_string()))?;
+                opts.survey_interval = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|&secs| secs > 0)
+                        .ok_or_else(|| Error::Usage(format!("Invalid --survey-interval '{}'", value)))?,
+                );
+            }
+            "--password" => {
+                // Arguments are visible to every user through /proc
+                return Err(Error::Usage(
+                    "Passwords are not accepted as arguments; use --password-file or --ask-password"
+                        .to_string(),
+                ));
+            }
+            "--security" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| Error::Usage("--security requires a value".to_string()))?;
+                opts.security = Some(SecurityFilter::parse(&value)?);
+            }
+            "--agent" => opts.agent = true,
+            "--secret-agent" => opts.secret_agent = true,
+            "--daemon" => opts.daemon = true,
+            "--no-daemon" => opts.no_daemon = true,
+            "--notify" => opts.notify = true,
             "--interface" => {
                 let value = args
                     .next()
-                    .ok_or_else(|| "--interface requires a value".to_string())?;
+                    .ok_or_else(|| Error::Usage("--interface requires a value".to_string()))?;
                 opts.interface = Some(value);
             }
+            "--json" => opts.format = OutputFormat::Json,
+            "--format" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| Error::Usage("--format requires a value".to_string()))?;
+                // Resolved after the loop: --bar takes bar formats instead
+                format_arg = Some(value);
+            }
+            "--theme" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| Error::Usage("--theme requires a value".to_string()))?;
+                opts.theme = Some(config::Theme::parse(&value)?);
+            }
+            "--icons" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| Error::Usage("--icons requires a value".to_string()))?;
+                opts.icons = Some(config::IconSet::parse(&value)?);
+            }
+            "--bar" => opts.bar = true,
+            "--watch" => opts.watch = true,
+            "--print-default-config" => {
+                print!("{}", config::DEFAULT_CONFIG);
+                std::process::exit(0);
+            }
             "--help" | "-h" => {
                 print_usage();
                 std::process::exit(0);
             }
-            _ => return Err(format!("Unknown argument: {}", arg)),
+            _ => return Err(Error::Usage(format!("Unknown argument: {}", arg))),
         }
     }
 
-    let action_count = [opts.rescan, opts.disconnect, opts.status]
-        .iter()
-        .filter(|&&flag| flag)
-        .count();
+    if let Some(value) = format_arg {
+        if opts.bar {
+            opts.bar_format = bar::BarFormat::parse(&value)?;
+        } else {
+            opts.format = OutputFormat::parse(&value)?;
+        }
+    }
+    // Resolved after the loop too, since --hex may come after the SSID
+    match ssid_arg {
+        Some((arg, value)) => {
+            let ssid = if opts.hex {
+                Ssid::from_hex(&value).ok_or_else(|| Error::Usage(format!("Invalid hex SSID '{}'", value)))?
+            } else {
+                Ssid::from(value.as_str())
+            };
+            if arg == "--connect" {
+                opts.connect = Some(ssid);
+            } else {
+                opts.forget = Some(ssid);
+            }
+        }
+        None if opts.hex => return Err(Error::Usage("--hex only works with --connect or --forget".to_string())),
+        None => {}
+    }
+    if opts.watch && !opts.bar {
+        return Err(Error::Usage("--watch only works with --bar".to_string()));
+    }
+    if opts.survey_interval.is_some() && opts.survey.is_none() {
+        return Err(Error::Usage("--survey-interval only works with --survey".to_string()));
+    }
+
+    // With --list, --rescan only refreshes the results before listing
+    let rescan_action = opts.rescan && !opts.list;
+    let action_count = [
+        rescan_action,
+        opts.disconnect,
+        opts.status,
+        opts.list,
+        opts.connect.is_some(),
+        opts.forget.is_some(),
+        opts.dmenu.is_some(),
+        opts.bar,
+        opts.secret_agent,
+        opts.daemon,
+        opts.history,
+    ]
+    .iter()
+    .filter(|&&flag| flag)
+    .count();
     if action_count > 1 {
-        return Err("Use only one non-interactive action at a time".to_string());
+        return Err(Error::Usage("Use only one non-interactive action at a time".to_string()));
+    }
+    if action_count > 0 && opts.survey.is_some() {
+        return Err(Error::Usage("--survey runs in the TUI and can't be combined with other actions".to_string()));
+    }
+    if opts.password_file.is_some() && opts.ask_password {
+        return Err(Error::Usage("Use either --password-file or --ask-password".to_string()));
     }
 
     Ok(opts)
 }
 
-fn get_wifi_interfaces() -> Vec<String> {
-    let output = match run_command("nmcli", &["-t", "-f", "DEVICE,TYPE,STATE", "device", "status"]) {
-        Ok(o) => o,
-        Err(_) => return Vec::new(),
-    };
+fn get_wifi_interfaces() -> Result<Vec<String>, Error> {
+    if let Some(result) = daemon::call("interfaces", json!({})) {
+        return result;
+    }
+    let output = run_command("nmcli", &["-t", "-f", "DEVICE,TYPE,STATE", "device", "status"])?;
 
     let mut interfaces = Vec::new();
     for line in output.lines() {
@@ -219,13 +772,18 @@ fn get_wifi_interfaces() -> Vec<String> {
             interfaces.push(parts[0].to_string());
         }
     }
-    interfaces
+    Ok(interfaces)
 }
 
 fn pick_default_interface(interfaces: &[String]) -> Option<String> {
     if interfaces.is_empty() {
         return None;
     }
+    if let Some(preferred) = &config::get().behavior.default_interface {
+        if interfaces.contains(preferred) {
+            return Some(preferred.clone());
+        }
+    }
 
     let output = run_command("nmcli", &["-t", "-f", "DEVICE,TYPE,STATE", "device", "status"]).ok()?;
     for line in output.lines() {
@@ -244,9 +802,12 @@ fn pick_default_interface(interfaces: &[String]) -> Option<String> {
     Some(interfaces[0].clone())
 }
 
-fn run_status(interface: Option<&str>) -> Result<String, String> {
+fn run_status(interface: Option<&str>) -> Result<StatusReport, Error> {
+    if let Some(result) = daemon::call("status", json!({ "interface": interface })) {
+        return result;
+    }
     let output = run_command("nmcli", &["-t", "-f", "DEVICE,TYPE,STATE,CONNECTION", "device", "status"])?;
-    let mut rows = Vec::new();
+    let mut devices = Vec::new();
 
     for line in output.lines() {
         let parts: Vec<&str> = line.split(':').collect();
@@ -258,245 +819,1330 @@ fn run_status(interface: Option<&str>) -> Result<String, String> {
                 continue;
             }
         }
-        rows.push(format!(
-            "interface={} state={} connection={}",
-            parts[0], parts[2], parts[3]
-        ));
+        devices.push(DeviceStatus {
+            interface: parts[0].to_string(),
+            state: parts[2].to_string(),
+            connection: Some(parts[3].to_string()).filter(|c| !c.is_empty() && c != "--"),
+        });
     }
 
-    if rows.is_empty() {
+    if devices.is_empty() {
         if let Some(iface) = interface {
-            return Ok(format!("interface={} state=unavailable connection=--", iface));
-        }
-        return Ok("interface=none state=unavailable connection=--".to_string());
-    }
-
-    Ok(rows.join("\n"))
-}
-
-fn get_networks(interface: &str) -> Vec<Network> {
-    // Format: IN-USE:SSID:BSSID:SECURITY:SIGNAL
-    let output = match run_command(
-        "nmcli",
-        &[
-            "-t",
-            "-f",
-            "IN-USE,SSID,BSSID,SECURITY,SIGNAL",
-            "dev",
-            "wifi",
-            "list",
-            "ifname",
-            interface,
-        ],
-    ) {
-        Ok(o) => o,
-        Err(_) => return Vec::new(),
+            devices.push(DeviceStatus {
+                interface: iface.to_string(),
+                state: "unavailable".to_string(),
+                connection: None,
+            });
+        }
+    }
+
+    Ok(StatusReport { devices })
+}
+
+/// Scan results for the TUI, with the network list filtered by the
+/// interface's defaults. The order is left to `App::apply_filter`, which
+/// sorts by the current key.
+fn get_networks(interface: &str) -> Scan {
+    let scan = match daemon::call("access_points", json!({ "interface": interface })) {
+        Some(access_points) => scan_networks(interface).map(|networks| Scan {
+            access_points: access_points.unwrap_or_default(),
+            networks,
+        }),
+        None => scan_interface(interface),
     };
+    let mut scan = scan.unwrap_or_default();
+    if let Some(defaults) = config::get().interface(interface) {
+        scan.networks.retain(|n| {
+            n.signal >= defaults.min_signal && defaults.security.is_none_or(|filter| filter.matches(&n.security))
+        });
+    }
+    scan
+}
+
+/// Sorts by `key`, keeping the active network on top. Ties go to the
+/// stronger signal.
+fn sort_networks(networks: &mut [Network], key: config::SortKey, reverse: bool) {
+    use config::SortKey;
+    networks.sort_by(|a, b| {
+        let order = match key {
+            SortKey::Signal => b.signal.cmp(&a.signal),
+            SortKey::Ssid => a.ssid.to_string().to_lowercase().cmp(&b.ssid.to_string().to_lowercase()),
+            SortKey::Security => security_rank(&b.security).cmp(&security_rank(&a.security)),
+            SortKey::Band => (Band::of(a.freq), a.chan).cmp(&(Band::of(b.freq), b.chan)),
+            SortKey::Saved => b.saved.cmp(&a.saved),
+            SortKey::LastSeen => a.last_seen.unwrap_or(u64::MAX).cmp(&b.last_seen.unwrap_or(u64::MAX)),
+        };
+        let order = if reverse { order.reverse() } else { order };
+        b.in_use.cmp(&a.in_use).then(order).then(b.signal.cmp(&a.signal))
+    });
+}
+
+/// Higher is more secure.
+fn security_rank(security: &str) -> u8 {
+    if security.contains("WPA3") {
+        4
+    } else if security.contains("WPA2") {
+        3
+    } else if security.contains("WPA") {
+        2
+    } else if security.contains("WEP") {
+        1
+    } else {
+        0
+    }
+}
+
+fn scan_networks(interface: &str) -> Result<Vec<Network>, Error> {
+    if let Some(result) = daemon::call("list", json!({ "interface": interface })) {
+        return result;
+    }
+    scan_interface(interface).map(|scan| scan.networks)
+}
+
+/// Runs nmcli for both halves of a `Scan`.
+fn scan_interface(interface: &str) -> Result<Scan, Error> {
+    let output = run_command("nmcli", &["-t", "-f", SCAN_FIELDS, "dev", "wifi", "list", "ifname", interface])?;
+    let access_points = parse_access_points(&output);
+    // Only kept for `--history`, so a failure here isn't fatal either
+    let _ = history::record(&access_points);
+    let mut networks = unique_networks(access_points.clone());
+    // Only used for display and filtering, so a failure here isn't fatal
+    let saved = saved_connections().unwrap_or_default();
+    for net in &mut networks {
+        net.saved = saved.contains(&net.ssid.profile_name());
+    }
+    read_last_seen(&mut networks);
+    Ok(Scan { access_points, networks })
+}
+
+/// Fills in `last_seen` from NetworkManager's access point objects. nmcli
+/// doesn't print it, and without the system bus it just stays unknown.
+fn read_last_seen(networks: &mut [Network]) {
+    if let Some(bus) = system_bus() {
+        fill_last_seen(bus, networks);
+    }
+}
+
+fn fill_last_seen(bus: &zbus::blocking::Connection, networks: &mut [Network]) {
+    use std::collections::HashMap;
+    use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+    type ManagedObjects = HashMap<OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>>;
+
+    if networks.iter().all(|n| n.ap_path.is_empty()) {
+        return;
+    }
+    // LastSeen counts CLOCK_BOOTTIME seconds, and so does /proc/uptime
+    let uptime = fs::read_to_string("/proc/uptime").ok();
+    let Some(now) = uptime.as_deref().and_then(|u| u.split('.').next()?.parse::<i64>().ok()) else { return };
+
+    // One call for every access point, rather than one per access point on every scan
+    let reply = bus.call_method(
+        Some("org.freedesktop.NetworkManager"),
+        "/org/freedesktop",
+        Some("org.freedesktop.DBus.ObjectManager"),
+        "GetManagedObjects",
+        &(),
+    );
+    let Some(objects) = reply.ok().and_then(|msg| msg.body().deserialize::<ManagedObjects>().ok()) else { return };
+
+    for net in networks.iter_mut().filter(|n| !n.ap_path.is_empty()) {
+        let seen = OwnedObjectPath::try_from(net.ap_path.as_str())
+            .ok()
+            .and_then(|path| objects.get(&path))
+            .and_then(|interfaces| interfaces.get("org.freedesktop.NetworkManager.AccessPoint"))
+            .and_then(|properties| properties.get("LastSeen"))
+            .and_then(|value| i32::try_from(value).ok());
+        // -1 means never
+        net.last_seen = seen.filter(|&s| s >= 0).map(|s| (now - i64::from(s)).max(0) as u64);
+    }
+}
+
+/// Shared connection for the few things nmcli can't tell us.
+fn system_bus() -> Option<&'static zbus::blocking::Connection> {
+    static SYSTEM_BUS: OnceLock<Option<zbus::blocking::Connection>> = OnceLock::new();
+    SYSTEM_BUS.get_or_init(|| zbus::blocking::Connection::system().ok()).as_ref()
+}
+
+/// UUIDs of the saved profiles for exactly this SSID. nmcli only shows SSIDs
+/// as text, so the profiles' settings are read over D-Bus; `None` if the
+/// system bus isn't available.
+fn saved_connection_uuids(ssid: &Ssid) -> Option<Vec<String>> {
+    use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+    type Settings = std::collections::HashMap<String, std::collections::HashMap<String, OwnedValue>>;
+
+    let bus = system_bus()?;
+    let paths: Vec<OwnedObjectPath> = bus
+        .call_method(
+            Some("org.freedesktop.NetworkManager"),
+            "/org/freedesktop/NetworkManager/Settings",
+            Some("org.freedesktop.NetworkManager.Settings"),
+            "ListConnections",
+            &(),
+        )
+        .ok()?
+        .body()
+        .deserialize()
+        .ok()?;
+
+    let mut uuids = Vec::new();
+    for path in paths {
+        let reply = bus.call_method(
+            Some("org.freedesktop.NetworkManager"),
+            path.as_str(),
+            Some("org.freedesktop.NetworkManager.Settings.Connection"),
+            "GetSettings",
+            &(),
+        );
+        let Some(settings) = reply.ok().and_then(|msg| msg.body().deserialize::<Settings>().ok()) else { continue };
+        let setting = |group: &str, key: &str| settings.get(group).and_then(|g| g.get(key));
+        let bytes = match setting("802-11-wireless", "ssid").map(|v| &**v) {
+            Some(Value::Array(bytes)) => bytes.iter().map(|b| u8::try_from(b).ok()).collect::<Option<Vec<u8>>>(),
+            _ => None,
+        };
+        if bytes.as_deref() != Some(ssid.as_bytes()) {
+            continue;
+        }
+        if let Some(uuid) = setting("connection", "uuid").and_then(|v| <&str>::try_from(&**v).ok()) {
+            uuids.push(uuid.to_string());
+        }
+    }
+    Some(uuids)
+}
+
+/// Names of the saved Wi-Fi connection profiles.
+fn saved_connections() -> Result<Vec<String>, Error> {
+    let output = run_command("nmcli", &["-t", "-f", "NAME,TYPE", "connection", "show"])?;
+    Ok(output
+        .lines()
+        .filter_map(|line| line.rsplit_once(':'))
+        .filter(|(_, kind)| *kind == "802-11-wireless")
+        .map(|(name, _)| name.replace("\\:", ":"))
+        .collect())
+}
+
+/// Fields asked of `nmcli dev wifi list`, in the order `parse_access_points` expects.
+const SCAN_FIELDS: &str = "IN-USE,SSID,BSSID,SECURITY,SIGNAL,FREQ,CHAN,DBUS-PATH,SSID-HEX";
+
+/// Parses `nmcli -t -f <SCAN_FIELDS> dev wifi list` output into a
+/// deduplicated list, connected network first.
+fn parse_networks(output: &str) -> Vec<Network> {
+    unique_networks(parse_access_points(output))
+}
 
+/// One entry per SSID, connected network first, hidden networks left out.
+fn unique_networks(access_points: Vec<Network>) -> Vec<Network> {
     let mut networks = Vec::new();
     let mut seen_ssids = Vec::new();
 
+    for net in access_points {
+        if net.ssid.is_empty() { continue; }
+
+        // Deduplicate by SSID, preferring the connected one or stronger signal
+        if seen_ssids.contains(&net.ssid) && !net.in_use { 
+            continue; 
+        }
+        seen_ssids.push(net.ssid.clone());
+        networks.push(net);
+    }
+    // Sort: Connected first, then Signal strength
+    networks.sort_by(|a, b| {
+        if a.in_use != b.in_use {
+            b.in_use.cmp(&a.in_use)
+        } else {
+            b.signal.cmp(&a.signal)
+        }
+    });
+    networks
+}
+
+/// Every access point in the output, one per BSSID and including hidden
+/// ones. A BANDWIDTH column after SSID-HEX is picked up if it was asked for.
+fn parse_access_points(output: &str) -> Vec<Network> {
+    let mut access_points = Vec::new();
+
     for line in output.lines() {
         let safe_line = line.replace("\\:", "\u{0000}");
         let parts: Vec<&str> = safe_line.split(':').collect();
         if parts.len() < 5 { continue; }
 
         let in_use = parts[0] == "*";
-        // Do not trim SSID; significant whitespace might exist
-        let ssid = parts[1].replace("\u{0000}", ":").to_string();
+        // Do not trim SSID; significant whitespace might exist. The text
+        // column is lossy for names that aren't UTF-8, the hex one isn't.
+        let ssid = parts
+            .get(8)
+            .and_then(|hex| Ssid::from_hex(hex))
+            .unwrap_or_else(|| Ssid::from(parts[1].replace("\u{0000}", ":").as_str()));
         let bssid = parts[2].replace("\u{0000}", ":");
         let security = parts[3].replace("\u{0000}", ":");
         let signal: u8 = parts[4].parse().unwrap_or(0);
-
-        if ssid.is_empty() { continue; }
-        
-        // Deduplicate by SSID, preferring the connected one or stronger signal
-        if seen_ssids.contains(&ssid) && !in_use { 
-            continue; 
-        }
-        seen_ssids.push(ssid.clone());
-
-        networks.push(Network {
+        // "5180 MHz"
+        let freq = parts.get(5).and_then(|f| f.trim_end_matches(" MHz").parse().ok()).unwrap_or(0);
+        let chan = parts.get(6).and_then(|c| c.parse().ok()).unwrap_or(0);
+        let ap_path = parts.get(7).unwrap_or(&"").to_string();
+        // "80 MHz"
+        let bandwidth = parts.get(9).and_then(|b| b.trim_end_matches(" MHz").parse().ok()).unwrap_or(0);
+
+        access_points.push(Network {
             ssid,
             bssid,
             security,
             signal,
             in_use,
+            freq,
+            chan,
+            bandwidth,
+            saved: false,
+            last_seen: None,
+            ap_path,
         });
     }
-    // Sort: Connected first, then Signal strength
-    networks.sort_by(|a, b| {
-        if a.in_use != b.in_use {
-            b.in_use.cmp(&a.in_use)
-        } else {
-            b.signal.cmp(&a.signal)
-        }
-    });
-    networks
+    access_points
+}
+
+/// Every access point the interface sees, for the channel view. Older nmcli
+/// doesn't know BANDWIDTH, in which case all widths are left unknown.
+fn scan_access_points(interface: &str) -> Result<Vec<Network>, Error> {
+    let fields = format!("{},BANDWIDTH", SCAN_FIELDS);
+    let output = run_command("nmcli", &["-t", "-f", &fields, "dev", "wifi", "list", "ifname", interface])
+        .or_else(|_| run_command("nmcli", &["-t", "-f", SCAN_FIELDS, "dev", "wifi", "list", "ifname", interface]))?;
+    let access_points = parse_access_points(&output);
+    let _ = history::record(&access_points);
+    Ok(access_points)
+}
+
+fn run_list(interface: &str, opts: &CliOptions) -> Result<NetworkList, Error> {
+    if opts.rescan {
+        rescan_interface(interface)?;
+    }
+    // Command-line filters win over the per-interface defaults
+    let defaults = config::get().interface(interface);
+    let min_signal = opts.min_signal.or(defaults.map(|d| d.min_signal)).unwrap_or(0);
+    let security = opts.security.or(defaults.and_then(|d| d.security));
+    let mut networks: Vec<Network> = scan_networks(interface)?
+        .into_iter()
+        .filter(|n| n.signal >= min_signal)
+        .filter(|n| security.is_none_or(|filter| filter.matches(&n.security)))
+        .collect();
+    let behavior = &config::get().behavior;
+    sort_networks(&mut networks, behavior.sort, behavior.sort_reverse);
+    Ok(NetworkList {
+        interface: interface.to_string(),
+        networks,
+    })
+}
+
+fn run_history(opts: &CliOptions) -> Result<HistoryReport, Error> {
+    let networks = history::load()?
+        .into_iter()
+        .filter(|s| opts.bssid.as_ref().is_none_or(|bssid| s.bssid.eq_ignore_ascii_case(bssid)))
+        .map(|s| HistoryEntry {
+            first_seen: utc_timestamp(s.first_seen),
+            last_seen: utc_timestamp(s.last_seen),
+            bssid: s.bssid,
+            ssid: s.ssid,
+            security: s.security,
+            best_signal: s.best_signal,
+            channel: s.chan,
+        })
+        .collect();
+    Ok(HistoryReport { networks })
 }
 
 fn connect_network(
-    ssid: &str,
+    ssid: &Ssid,
     bssid: &str,
     password: &str,
     security: &str,
     interface: &str,
-) -> Result<String, String> {
+) -> Result<String, Error> {
+    // The socket is private to the user, so the password can go over it
+    let params = json!({
+        "ssid": ssid,
+        "bssid": bssid,
+        "password": password,
+        "security": security,
+        "interface": interface,
+    });
+    if let Some(result) = daemon::call("connect", params) {
+        return result;
+    }
+
     // Strategy: Use 'dev wifi connect' with BSSID for precision, but name the profile with SSID.
-    
+    let name = ssid.profile_name();
+    // An SSID that isn't plain text can't go through nmcli's arguments
+    // intact, but nmcli also accepts the BSSID in its place
+    let target = match ssid.as_text() {
+        Some(text) => text,
+        None if !bssid.is_empty() => bssid,
+        None => return Err(Error::Usage(format!("'{}' can only be connected to by BSSID", ssid))),
+    };
+
     // 1. Delete existing profile to avoid conflicts (e.g. stale key-mgmt settings)
-    let _ = run_command("nmcli", &["connection", "delete", ssid]);
+    let _ = run_command("nmcli", &["connection", "delete", &name]);
+
+    let ask = needs_password(security);
+    let args = connect_args(target, interface, bssid, &name, ask);
+    let result = if ask {
+        run_command_with_input("nmcli", &args, &format!("{}\n", password))
+    } else {
+        run_command("nmcli", &args)
+    };
+    let result = result.map_err(|e| match e {
+        Error::Command(failure) => {
+            // A disabled radio surfaces as a generic "device not available"
+            let kind = if wifi_radio_enabled() == Some(false) {
+                ConnectError::RadioOff
+            } else {
+                ConnectError::from_nmcli(failure.status, &failure.stderr)
+            };
+            Error::Connect { kind, failure: Some(failure) }
+        }
+        other => other,
+    });
+    match &result {
+        Ok(_) => hooks::connected(interface, &ssid.to_string(), bssid),
+        Err(e) if hooks::is_reportable(password, e) => hooks::failed(interface, &ssid.to_string(), bssid, e),
+        Err(_) => {}
+    }
+    result
+}
 
-    let mut args = vec!["dev", "wifi", "connect", ssid, "ifname", interface];
+/// Arguments for `nmcli dev wifi connect`. The password never goes on the
+/// command line, where any user could read it from the process list; with
+/// `ask`, nmcli prompts for it and reads it from stdin instead.
+fn connect_args<'a>(target: &'a str, interface: &'a str, bssid: &'a str, name: &'a str, ask: bool) -> Vec<&'a str> {
+    let mut args = Vec::new();
+    if ask {
+        args.push("--ask");
+    }
+    args.extend(["dev", "wifi", "connect", target, "ifname", interface]);
     if !bssid.is_empty() {
         args.push("bssid");
         args.push(bssid);
     }
     args.push("name");
-    args.push(ssid);
-    
-    // Only add password argument if the network is secured
-    if security.contains("WPA") || security.contains("RSN") || security.contains("WEP") {
-        args.push("password");
-        args.push(password);
+    args.push(name);
+    args
+}
+
+fn wifi_radio_enabled() -> Option<bool> {
+    let output = run_command("nmcli", &["radio", "wifi"]).ok()?;
+    Some(output.trim() == "enabled")
+}
+
+fn run_connect(interface: &str, ssid: &Ssid, opts: &CliOptions) -> Result<ActionReport, Error> {
+    // Look the network up so we know its security and a BSSID to pin
+    let scanned = scan_networks(interface)?.into_iter().find(|n| n.ssid == *ssid);
+
+    let password = if let Some(path) = &opts.password_file {
+        Some(read_password_file(path)?)
+    } else if opts.ask_password {
+        Some(prompt_password(&format!("Password for {}: ", ssid))?)
+    } else {
+        None
+    };
+
+    let security = match (&scanned, &password) {
+        (Some(net), _) => net.security.clone(),
+        // Not in the scan results; let nmcli decide, passing the password if we have one
+        (None, Some(_)) => "WPA".to_string(),
+        (None, None) => String::new(),
+    };
+    if let Some(password) = &password {
+        validate_password(&security, password).map_err(Error::Usage)?;
     }
+    let bssid = opts
+        .bssid
+        .clone()
+        .or_else(|| scanned.as_ref().map(|n| n.bssid.clone()))
+        .unwrap_or_default();
+
+    let out = connect_network(ssid, &bssid, password.as_deref().unwrap_or(""), &security, interface)?;
+    let ip4 = match opts.wait {
+        Some(secs) => Some(wait_for_ip(interface, Duration::from_secs(secs))?),
+        None => None,
+    };
 
-    run_command("nmcli", &args)
+    Ok(ActionReport {
+        action: "connect",
+        interface: Some(interface.to_string()),
+        ssid: Some(ssid.to_string()),
+        ok: true,
+        message: Some(out).filter(|o| !o.is_empty()),
+        ip4,
+    })
 }
 
-fn delete_connection(ssid: &str) -> Result<String, String> {
-    // Try to find the connection name. Usually same as SSID or "SSID 1"
-    // Simple approach: delete by SSID, nmcli usually handles it.
-    // Better approach: Find active connection on interface.
-    // For this TUI, let's just try deleting the SSID profile.
-    run_command("nmcli", &["connection", "delete", ssid])
+fn read_password_file(path: &str) -> Result<String, Error> {
+    let mut contents = String::new();
+    let res = if path == "-" {
+        io::stdin().read_to_string(&mut contents).map(|_| ())
+    } else {
+        fs::read_to_string(path).map(|c| contents = c)
+    };
+    res.map_err(|source| Error::Io {
+        context: format!("Cannot read password from '{}'", path),
+        source,
+    })?;
+    Ok(contents.lines().next().unwrap_or("").to_string())
 }
 
-fn disconnect_interface(interface: &str) -> Result<String, String> {
-    run_command("nmcli", &["dev", "disconnect", interface])
+/// Reads a password from the terminal without echoing it, using the same
+/// editor as the TUI prompt.
+fn prompt_password(prompt: &str) -> Result<String, Error> {
+    let io_err = |source| Error::Io {
+        context: "Cannot read password from terminal".to_string(),
+        source,
+    };
+    eprint!("{}", prompt);
+    io::stderr().flush().map_err(io_err)?;
+
+    enable_raw_mode().map_err(io_err)?;
+    let mut editor = input::LineEditor::default();
+    let res = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Enter => break Ok(editor.text().to_string()),
+                KeyCode::Esc => break Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled")),
+                KeyCode::Char('c') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                    break Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"))
+                }
+                _ => {
+                    editor.handle_key(&key);
+                }
+            },
+            Ok(Event::Paste(text)) => editor.insert_str(&text),
+            Ok(_) => {}
+            Err(e) => break Err(e),
+        }
+    };
+    disable_raw_mode().map_err(io_err)?;
+    eprintln!();
+    res.map_err(io_err)
 }
 
-fn rescan_interface(interface: &str) -> Result<String, String> {
-    run_command("nmcli", &["dev", "wifi", "rescan", "ifname", interface])
+/// Polls the device until it is connected and has an IPv4 address.
+fn wait_for_ip(interface: &str, timeout: Duration) -> Result<String, Error> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let output = run_command(
+            "nmcli",
+            &["-t", "-f", "GENERAL.STATE,IP4.ADDRESS", "device", "show", interface],
+        )?;
+        let mut connected = false;
+        let mut address = None;
+        for line in output.lines() {
+            if let Some(state) = line.strip_prefix("GENERAL.STATE:") {
+                // e.g. "100 (connected)"
+                connected = state.starts_with("100");
+            } else if line.starts_with("IP4.ADDRESS") {
+                address = line.split_once(':').map(|(_, addr)| addr.to_string());
+            }
+        }
+        match address {
+            Some(addr) if connected && !addr.is_empty() => return Ok(addr),
+            _ if Instant::now() >= deadline => {
+                return Err(Error::Connect {
+                    kind: ConnectError::Timeout,
+                    failure: None,
+                })
+            }
+            _ => std::thread::sleep(Duration::from_millis(500)),
+        }
+    }
 }
 
+fn needs_password(security: &str) -> bool {
+    security.contains("WPA") || security.contains("RSN") || security.contains("WEP")
+}
 
-// --- UI Rendering ---
+/// Checks a password against what NetworkManager accepts for the security
+/// type, so typos are caught before we hand it to nmcli.
+fn validate_password(security: &str, password: &str) -> Result<(), String> {
+    let len = password.chars().count();
+    let is_hex = |n: usize| len == n && password.chars().all(|c| c.is_ascii_hexdigit());
+    let is_printable_ascii = password.chars().all(|c| (' '..='~').contains(&c));
+
+    if security.contains("802.1X") || (security.contains("WPA3") && !security.contains("WPA2")) {
+        // Enterprise and SAE passwords have no fixed length rules
+        if password.is_empty() {
+            return Err("Password cannot be empty".to_string());
+        }
+    } else if security.contains("WEP") {
+        if !(is_hex(10) || is_hex(26) || (is_printable_ascii && (len == 5 || len == 13))) {
+            return Err("WEP key must be 5 or 13 characters, or 10 or 26 hex digits".to_string());
+        }
+    } else if needs_password(security) {
+        if !is_printable_ascii {
+            return Err("WPA passphrase may only contain printable ASCII".to_string());
+        }
+        if !(8..=63).contains(&len) && !is_hex(64) {
+            return Err(format!("WPA passphrase must be 8-63 characters or 64 hex digits ({} entered)", len));
+        }
+    }
+    Ok(())
+}
+
+fn delete_connection(ssid: &Ssid) -> Result<String, Error> {
+    if let Some(result) = daemon::call("forget", json!({ "ssid": ssid })) {
+        return result;
+    }
+    // Profiles are matched on the SSID bytes they connect to, whatever
+    // they're named. Without D-Bus, fall back to the name we'd have given it.
+    match saved_connection_uuids(ssid) {
+        Some(uuids) if !uuids.is_empty() => {
+            let mut args = vec!["connection", "delete"];
+            for uuid in &uuids {
+                args.push("uuid");
+                args.push(uuid);
+            }
+            run_command("nmcli", &args)
+        }
+        _ => run_command("nmcli", &["connection", "delete", &ssid.profile_name()]),
+    }
+}
+
+fn disconnect_interface(interface: &str) -> Result<String, Error> {
+    if let Some(result) = daemon::call("disconnect", json!({ "interface": interface })) {
+        return result;
+    }
+    // Hooks want to know which network went away
+    let active = run_status(Some(interface))
+        .ok()
+        .and_then(|report| report.devices.into_iter().next())
+        .and_then(|device| device.connection);
+    let out = run_command("nmcli", &["dev", "disconnect", interface])?;
+    hooks::disconnected(interface, active);
+    Ok(out)
+}
+
+fn rescan_interface(interface: &str) -> Result<String, Error> {
+    if let Some(result) = daemon::call("rescan", json!({ "interface": interface })) {
+        return result;
+    }
+    run_command("nmcli", &["dev", "wifi", "rescan", "ifname", interface])
+}
+
+/// Seconds since the Unix epoch.
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// RFC 3339 in UTC, to the second: "2024-05-01T09:30:00Z".
+fn utc_timestamp(secs: u64) -> String {
+    let (days, rest) = (secs / 86_400, secs % 86_400);
+    // Days since 1970-01-01 to a civil date, after Howard Hinnant's civil_from_days
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+
+// --- UI Rendering ---
+
+fn signal_icon(signal: u8) -> &'static str {
+    let steps = config::get().ui.signal_thresholds;
+    theme::icons().signal[steps.iter().filter(|&&step| signal > step).count()]
+}
+
+fn lock_icon(security: &str) -> &'static str {
+    let icons = theme::icons();
+    if security.is_empty() { icons.open } else { icons.lock }
+}
+
+fn active_marker(in_use: bool) -> &'static str {
+    let icons = theme::icons();
+    if in_use { icons.active } else { icons.inactive }
+}
 
 fn ui(f: &mut Frame, app: &App) {
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Min(0), Constraint::Length(1)])
-        .split(f.area());
+    let chunks = screen_layout(f.area(), app);
 
     // Network List
+    let palette = theme::palette();
+    // Inside the borders
+    let list_width = chunks[1].width.saturating_sub(2) as usize;
+    let (columns, ssid_width) = list_layout(list_width, &app.networks);
     let items: Vec<ListItem> = app.networks.iter().map(|n| {
-        let lock = if n.security.is_empty() { " " } else { "" };
-        let signal_icon = match n.signal {
-            0..=20 => "󰤯",
-            21..=40 => "󰤟",
-            41..=60 => "󰤢",
-            61..=80 => "󰤥",
-            _ => "󰤨",
-        };
-        let active = if n.in_use { " " } else { "  " };
-        
-        let display_ssid = if n.ssid.len() > MAX_SSID_DISPLAY_LEN && MAX_SSID_DISPLAY_LEN > 3 {
-            format!("{:.width$}...", &n.ssid[..MAX_SSID_DISPLAY_LEN - 3], width = MAX_SSID_DISPLAY_LEN - 3)
-        } else {
-            // Pad to MAX_SSID_DISPLAY_LEN if not truncated to maintain column width
-            let mut s = n.ssid.clone();
-            s.truncate(MAX_SSID_DISPLAY_LEN); // Ensure it doesn't exceed if it was just slightly longer than display_len
-            s
-        };
-        // Use `MAX_SSID_DISPLAY_LEN` for formatting width
-        let content = format!("{} {} {:<width$} {:>3}% {}", active, signal_icon, display_ssid, n.signal, lock, width = MAX_SSID_DISPLAY_LEN);
+        let mut content = Vec::new();
+        for (i, column) in columns.iter().enumerate() {
+            if i > 0 {
+                content.push(Span::raw(" "));
+            }
+            match column {
+                config::Column::Active => content.push(Span::raw(active_marker(n.in_use))),
+                config::Column::Bars => content.push(Span::raw(signal_icon(n.signal))),
+                config::Column::Ssid => {
+                    let ssid = n.ssid.to_string();
+                    let (padded, shown) = fit_width(&ssid, ssid_width);
+                    // Matches in the part cut off by "..." aren't shown
+                    let matched: Vec<usize> = filter::fuzzy_match(&app.filter.query, &ssid)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter(|&pos| pos < shown)
+                        .collect();
+                    content.extend(highlight(padded, &matched, palette.matched));
+                }
+                config::Column::Signal => content.push(Span::raw(format!("{:>3}%", n.signal))),
+                config::Column::Security => content.push(Span::raw(lock_icon(&n.security))),
+            }
+        }
         let style = if n.in_use { 
-            Style::default().fg(Color::Green)
+            palette.active
         } else { 
             Style::default() 
         };
-        ListItem::new(content).style(style)
+        ListItem::new(Line::from(content)).style(style)
     }).collect();
 
     let list = List::new(items)
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title(format!(" Wi-Fi Networks ({}) ", app.current_interface)),
+                .title(format!(
+                    " Wi-Fi Networks ({}) by {} {} ",
+                    app.current_interface,
+                    app.sort.name(),
+                    if app.sort_reverse { theme::icons().reversed } else { theme::icons().sorted }
+                )),
         )
-        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD));
+        .highlight_style(palette.selected);
+
+    f.render_stateful_widget(list, chunks[1], &mut app.list_state.clone());
 
-    f.render_stateful_widget(list, chunks[0], &mut app.list_state.clone());
+    if chunks[0].height > 0 {
+        let mut chips = vec![Span::raw(" ")];
+        for chip in app.filter.chips() {
+            chips.push(Span::styled(format!(" {} ", chip), palette.chip));
+            chips.push(Span::raw(" "));
+        }
+        chips.push(Span::raw(format!("{} of {}", app.networks.len(), app.scanned.len())));
+        f.render_widget(Paragraph::new(Line::from(chips)), chunks[0]);
+    }
 
     // Status Bar
     let status_style = match app.mode {
-        AppMode::Processing(_) => Style::default().bg(Color::Yellow).fg(Color::Black),
-        AppMode::Message(_) => Style::default().bg(Color::Blue),
-        _ => Style::default().bg(Color::White).fg(Color::Black),
+        AppMode::Processing(_) => palette.busy,
+        AppMode::Message(_) => palette.message,
+        _ => palette.status,
+    };
+    // Key hints come from the active keymap, so remapped keys show up here.
+    // Those that don't fit are left out, most important first.
+    let keymap = &config::get().keymap;
+    let status_width = chunks[3].width as usize;
+    let with_hints = |prefix: String, mode: Mode, actions: &[Action]| {
+        let room = status_width.saturating_sub(prefix.width() + 1);
+        let hint = keymap.hint_within(mode, actions, room);
+        format!("{}{} ", prefix, hint)
     };
+    let browse_actions = [
+        Action::Help,
+        Action::Connect,
+        Action::Search,
+        Action::Rescan,
+        Action::CycleInterface,
+        Action::Quit,
+    ];
+    let interface_prefix = match &app.survey {
+        Some(survey) if app.survey_scan.is_some() => {
+            format!(" IF:{} | {} {} | ", app.current_interface, survey.status(), theme::icons().busy)
+        }
+        Some(survey) => format!(" IF:{} | {} | ", app.current_interface, survey.status()),
+        None => format!(" IF:{} | ", app.current_interface),
+    };
+    let mut field_cursor = 0;
     let status_text = match &app.mode {
-        AppMode::Processing(msg) => format!(" 󰑐 {} ", msg),
-        AppMode::Message(msg) => format!(" 󰋗 {} (Press Any Key)", msg),
-        AppMode::PasswordInput => " Enter Password | Tab: Show/Hide | Esc: Cancel ".to_string(),
-        AppMode::ActionMenu => " Select Action ".to_string(),
+        AppMode::Processing(msg) => format!(" {} {} ", theme::icons().busy, msg),
+        AppMode::Message(msg) => format!(" {} {} (Press Any Key)", theme::icons().message, msg),
+        AppMode::PasswordInput => with_hints(
+            " Enter Password | ".to_string(),
+            Mode::Password,
+            &[Action::TogglePassword, Action::Cancel],
+        ),
+        AppMode::ActionMenu => with_hints(
+            " Select Action | ".to_string(),
+            Mode::ActionMenu,
+            &[Action::Select, Action::Cancel, Action::Help],
+        ),
+        AppMode::ConfirmForget => format!(
+            " Forget {}? {} | any other key: No ",
+            app.target_ssid,
+            keymap.hint(Mode::Confirm, &[Action::Confirm])
+        ),
+        AppMode::Search => {
+            let prefix = " Search: /";
+            let hint = format!(" | {} ", keymap.hint(Mode::Search, &[Action::Submit, Action::Cancel]));
+            let width = status_width.saturating_sub(prefix.len() + hint.len()).max(1);
+            let (query, cursor) = app.search_input.visible(width, false);
+            field_cursor = prefix.len() + cursor;
+            format!("{}{:<width$}{}", prefix, query, hint, width = width)
+        }
+        AppMode::Waypoint => {
+            let prefix = " Waypoint: ";
+            let hint = format!(" | {} ", keymap.hint(Mode::Waypoint, &[Action::Submit, Action::Cancel]));
+            let width = status_width.saturating_sub(prefix.len() + hint.len()).max(1);
+            let (name, cursor) = app.waypoint_input.visible(width, false);
+            field_cursor = prefix.len() + cursor;
+            format!("{}{:<width$}{}", prefix, name, hint, width = width)
+        }
+        AppMode::History => with_hints(
+            format!("{}History | ", interface_prefix),
+            Mode::History,
+            &[Action::Cancel, Action::Next, Action::Prev, Action::Help],
+        ),
+        AppMode::Channels => with_hints(
+            format!("{}{} | ", interface_prefix, app.channel_band.name()),
+            Mode::Channels,
+            &[Action::Next, Action::Rescan, Action::Cancel, Action::Help],
+        ),
+        AppMode::Help(_) => with_hints(
+            " Help | ".to_string(),
+            Mode::Help,
+            &[Action::Cancel, Action::Next, Action::Prev],
+        ),
         AppMode::Browsing => {
-            if let Some(idx) = app.list_state.selected() {
-                let selected_net = &app.networks[idx];
-                if selected_net.ssid.len() > MAX_SSID_DISPLAY_LEN {
-                    format!(
-                        " IF:{} | Full SSID: {} | i: Switch IF | r: Rescan | Enter: Connect | q: Quit ",
-                        app.current_interface, selected_net.ssid
-                    )
-                } else {
-                    format!(
-                        " IF:{} | i: Switch IF | r: Rescan | Enter: Connect | q: Quit ",
-                        app.current_interface
-                    )
-                }
-            } else {
-                format!(
-                    " IF:{} | i: Switch IF | r: Rescan | Enter: Connect | q: Quit ",
-                    app.current_interface
-                )
+            let long_ssid = app
+                .list_state
+                .selected()
+                .and_then(|idx| app.networks.get(idx))
+                .filter(|net| !columns.contains(&config::Column::Ssid) || net.ssid.to_string().width() > ssid_width);
+            match long_ssid {
+                Some(net) => with_hints(
+                    format!("{}Full SSID: {} | ", interface_prefix, net.ssid),
+                    Mode::Browse,
+                    &browse_actions,
+                ),
+                None => with_hints(interface_prefix, Mode::Browse, &browse_actions),
             }
         }
-        _ => format!(
-            " IF:{} | i: Switch IF | r: Rescan | Enter: Connect | q: Quit ",
-            app.current_interface
-        ),
+        _ => with_hints(interface_prefix, Mode::Browse, &browse_actions),
     };
     let status_bar = Paragraph::new(status_text).style(status_style);
-    f.render_widget(status_bar, chunks[1]);
+    f.render_widget(status_bar, chunks[3]);
+    if matches!(app.mode, AppMode::Search | AppMode::Waypoint) {
+        f.set_cursor_position((chunks[3].x + field_cursor as u16, chunks[3].y));
+    }
+
+    if chunks[2].height > 0 {
+        let selected = app.list_state.selected().and_then(|idx| app.networks.get(idx));
+        render_details(f, chunks[2], selected.map(|net| (net, app.trends.get(&net.bssid))));
+    }
+
+    // Covers everything but the status bar, and stays under its help
+    if matches!(app.mode, AppMode::Channels | AppMode::Help(Mode::Channels)) {
+        let area = Rect { height: chunks[3].y.saturating_sub(f.area().y), ..f.area() };
+        f.render_widget(Clear, area);
+        render_channels(f, area, app);
+    }
+
+    if matches!(app.mode, AppMode::History | AppMode::Help(Mode::History)) {
+        let area = history_area(f.area());
+        f.render_widget(Clear, area);
+        render_history(f, area, app);
+    }
 
     // Popups
     if app.mode == AppMode::PasswordInput {
-        let area = centered_rect(60, 20, f.area());
+        let area = password_area(f.area());
         f.render_widget(Clear, area); // Clear background
 
-        let password_display = if app.show_password {
-            app.input_buffer.clone()
+        let prompt = "Password: ";
+        let inner = Block::default().borders(Borders::ALL).inner(area);
+        let field_width = (inner.width as usize).saturating_sub(prompt.len());
+        let (password_display, cursor_col) = app.input_buffer.visible(field_width, !app.show_password);
+
+        // Live validation; stay quiet until something has been typed
+        let validation = if let Some(e) = &app.input_error {
+            Line::styled(e.clone(), palette.error)
         } else {
-            "*".repeat(app.input_buffer.len())
+            match validate_password(&app.target_security, app.input_buffer.text()) {
+                Ok(()) => Line::default(),
+                Err(e) if app.input_buffer.text().is_empty() => Line::styled(e, palette.hint),
+                Err(e) => Line::styled(e, palette.error),
+            }
         };
 
-        let input = Paragraph::new(format!("Password: {}\n\n(Tab to Show/Hide, Enter to Connect)", password_display))
-            .block(Block::default().borders(Borders::ALL).title(format!(" Connect to {} ", app.target_ssid)))
-            .style(Style::default().fg(Color::Yellow));
+        let popup_title = match &app.pending_secret {
+            Some(request) => format!(" NetworkManager: {} ({}) ", request.ssid, request.setting_name),
+            None => format!(" Connect to {} ", app.target_ssid),
+        };
+        // The hint line is the last one; see `password_toggle_area`
+        let input = Paragraph::new(vec![
+            Line::from(format!("{}{}", prompt, password_display)),
+            validation,
+            Line::default(),
+            Line::from(format!(
+                "({})",
+                keymap.hint(Mode::Password, &[Action::TogglePassword, Action::Submit])
+            )),
+        ])
+        .block(Block::default().borders(Borders::ALL).title(popup_title))
+        .style(palette.popup);
         f.render_widget(input, area);
+        f.set_cursor_position((inner.x + (prompt.len() + cursor_col) as u16, inner.y));
     }
     
     if app.mode == AppMode::ActionMenu {
-        let area = centered_rect(40, 25, f.area());
+        let area = action_menu_area(f.area());
         f.render_widget(Clear, area);
         
         let items: Vec<ListItem> = app.action_items.iter().map(|i| ListItem::new(*i)).collect();
         let list = List::new(items)
             .block(Block::default().borders(Borders::ALL).title(format!(" {} ", app.target_ssid)))
-            .highlight_style(Style::default().bg(Color::Red).fg(Color::White));
+            .highlight_style(palette.menu_selected);
         
         f.render_stateful_widget(list, area, &mut app.action_state.clone());
     }
+
+    if let AppMode::Help(mode) = app.mode {
+        let area = help_area(f.area());
+        f.render_widget(Clear, area);
+        let help = Paragraph::new(help_lines(mode))
+            .block(Block::default().borders(Borders::ALL).title(" Help "))
+            .scroll((app.help_scroll.min(help_max_scroll(f.area(), mode)), 0));
+        f.render_widget(help, area);
+    }
+}
+
+/// Narrowest the SSID column gets before other columns are dropped.
+const MIN_SSID_WIDTH: usize = 12;
+/// Columns given up on narrow terminals, first to go first.
+const COLUMN_DROP_ORDER: [config::Column; 4] =
+    [config::Column::Signal, config::Column::Security, config::Column::Active, config::Column::Bars];
+
+/// The configured columns that fit in `width` terminal columns, and the width
+/// of the SSID column. The SSID column is at least `ui.ssid_width` wide when
+/// there is room and grows to fit the longest name; when space runs out it
+/// shrinks to `MIN_SSID_WIDTH`, then the least important columns go.
+fn list_layout(width: usize, networks: &[Network]) -> (Vec<config::Column>, usize) {
+    let icons = theme::icons();
+    let column_width = |column: &config::Column| match column {
+        config::Column::Active => icons.active.width().max(icons.inactive.width()),
+        config::Column::Bars => icons.signal.iter().map(|icon| icon.width()).max().unwrap_or(0),
+        config::Column::Ssid => 0,
+        config::Column::Signal => 4,
+        config::Column::Security => icons.lock.width().max(icons.open.width()),
+    };
+    let longest = networks.iter().map(|n| n.ssid.to_string().width()).max().unwrap_or(0);
+    let preferred = config::get().ui.ssid_width.max(longest);
+
+    let mut columns = config::get().ui.columns.clone();
+    loop {
+        let fixed: usize = columns.iter().map(column_width).sum();
+        let gaps = columns.len().saturating_sub(1);
+        let available = width.saturating_sub(fixed + gaps);
+        if available >= MIN_SSID_WIDTH.min(preferred) || !columns.contains(&config::Column::Ssid) {
+            return (columns, preferred.min(available));
+        }
+        match COLUMN_DROP_ORDER.iter().find(|column| columns.contains(column)) {
+            Some(dropped) => columns.retain(|column| column != dropped),
+            None => return (columns, available),
+        }
+    }
+}
+
+/// Cuts `text` to `width` terminal columns, ending in "..." if anything was
+/// cut, and pads it to exactly `width`. Also returns how many chars of `text`
+/// were kept.
+fn fit_width(text: &str, width: usize) -> (String, usize) {
+    let mut fitted = String::new();
+    let mut kept = 0;
+    if text.width() <= width {
+        fitted.push_str(text);
+        kept = text.chars().count();
+    } else {
+        let ellipsis = if width >= 3 { "..." } else { "" };
+        let mut used = ellipsis.len();
+        for c in text.chars() {
+            let w = c.width().unwrap_or(0);
+            if used + w > width {
+                break;
+            }
+            fitted.push(c);
+            used += w;
+            kept += 1;
+        }
+        fitted.push_str(ellipsis);
+    }
+    // A wide char that didn't fit can leave one column over
+    let padding = width.saturating_sub(fitted.width());
+    fitted.extend(std::iter::repeat_n(' ', padding));
+    (fitted, kept)
+}
+
+/// Splits `text` into spans, styling the chars at `positions`.
+fn highlight(text: String, positions: &[usize], style: Style) -> Vec<Span<'static>> {
+    if positions.is_empty() {
+        return vec![Span::raw(text)];
+    }
+    let mut spans = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (i, c) in text.chars().enumerate() {
+        let matched = positions.contains(&i);
+        if matched != run_matched && !run.is_empty() {
+            let part = std::mem::take(&mut run);
+            spans.push(if run_matched { Span::styled(part, style) } else { Span::raw(part) });
+        }
+        run_matched = matched;
+        run.push(c);
+    }
+    spans.push(if run_matched { Span::styled(run, style) } else { Span::raw(run) });
+    spans
+}
+
+/// Rows of the details pane: borders, a line of numbers and the sparkline.
+const DETAILS_HEIGHT: u16 = 5;
+
+/// Filter chips row, network list, details pane and status bar.
+fn screen_layout(area: Rect, app: &App) -> std::rc::Rc<[Rect]> {
+    // The filter chips get a row of their own while any are on
+    let show_chips = app.filter.is_active() || app.mode == AppMode::Search;
+    let details_height = if app.show_details { DETAILS_HEIGHT } else { 0 };
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(show_chips as u16),
+            Constraint::Min(0),
+            Constraint::Length(details_height),
+            Constraint::Length(1),
+        ])
+        .split(area)
+}
+
+/// Signal history of the selected network: min/avg/max over the session and
+/// a sparkline of the latest scans.
+fn render_details(f: &mut Frame, area: Rect, selected: Option<(&Network, Option<&trend::Trend>)>) {
+    let Some((net, trend)) = selected else {
+        let block = Block::default().borders(Borders::ALL).title(" Details ");
+        f.render_widget(Paragraph::new("No network selected").block(block), area);
+        return;
+    };
+    let block = Block::default().borders(Borders::ALL).title(format!(" {} {} ", net.ssid, net.bssid));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+
+    let security = if net.security.is_empty() { "open" } else { &net.security };
+    let mut summary = format!("ch {} {} MHz {} | now {}%", net.chan, net.freq, security, net.signal);
+    if let Some(trend) = trend {
+        summary.push_str(&format!(
+            " | min {}% avg {}% max {}% over {} scans",
+            trend.min(),
+            trend.avg(),
+            trend.max(),
+            trend.count()
+        ));
+    }
+    f.render_widget(Paragraph::new(summary), rows[0]);
+
+    let data = trend.map(|t| t.recent(rows[1].width as usize)).unwrap_or_default();
+    let sparkline = Sparkline::default()
+        .data(&data)
+        .max(100)
+        .bar_set(theme::icons().sparkline.clone())
+        .style(theme::palette().active);
+    f.render_widget(sparkline, rows[1]);
+}
+
+/// Every access point on the band of the channel view as a box over the
+/// frequencies it covers, as high as its signal, with the least crowded
+/// channels underneath.
+fn render_channels(f: &mut Frame, area: Rect, app: &App) {
+    let band = app.channel_band;
+    let palette = theme::palette();
+    let [left, right, top, side] = theme::icons().channel;
+    let mut access_points: Vec<&Network> =
+        app.access_points.iter().filter(|ap| Band::of(ap.freq) == band).collect();
+    // Stronger ones are drawn last and stay readable
+    access_points.sort_by_key(|ap| ap.signal);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Channels on {} ({}) ", band.name(), app.current_interface));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1), Constraint::Length(1)])
+        .split(inner);
+    let (plot, axis_row, advice_row) = (rows[0], rows[1], rows[2]);
+    if plot.width < 2 || plot.height < 2 {
+        return;
+    }
+
+    let (low, high) = channels::axis(band);
+    let x_of = |freq: f64| {
+        let offset = ((freq - low) / (high - low) * f64::from(plot.width - 1)).round();
+        plot.x + offset.clamp(0.0, f64::from(plot.width - 1)) as u16
+    };
+    // The top row is kept for the labels of the strongest
+    let height_of = |signal: u8| (u32::from(signal) * u32::from(plot.height - 1) / 100).max(1) as u16;
+    let bottom = plot.y + plot.height - 1;
+
+    if access_points.is_empty() {
+        let text = format!("No access points on {}", band.name());
+        f.render_widget(Paragraph::new(text).alignment(Alignment::Center), Rect { y: plot.y + plot.height / 2, height: 1, ..plot });
+    }
+    let buf = f.buffer_mut();
+    let mut boxes = Vec::new();
+    for ap in &access_points {
+        let Some((from, to)) = channels::span(ap) else { continue };
+        let (x0, x1) = (x_of(from), x_of(to).max(x_of(from) + 1).min(plot.x + plot.width - 1));
+        let y = bottom + 1 - height_of(ap.signal);
+        let style = if ap.in_use { palette.active } else { Style::default() };
+        for x in x0 + 1..x1 {
+            buf.set_string(x, y, top, style);
+        }
+        for row in y + 1..=bottom {
+            buf.set_string(x0, row, side, style);
+            buf.set_string(x1, row, side, style);
+        }
+        buf.set_string(x0, y, left, style);
+        buf.set_string(x1, y, right, style);
+        boxes.push((ap, x0, x1, y, style));
+    }
+    // Labels go on top of all boxes, cut to the width of their own
+    for (ap, x0, x1, y, style) in boxes {
+        let label = if ap.ssid.is_empty() { "(hidden)".to_string() } else { ap.ssid.to_string() };
+        let width = (x1 - x0 + 1) as usize;
+        if y > plot.y && width >= 3 {
+            buf.set_stringn(x0, y - 1, label, width, style);
+        }
+    }
+
+    // Channel numbers, leaving out those that would run into the previous one
+    let mut free_from = axis_row.x;
+    for &chan in channels::labels(band) {
+        let text = chan.to_string();
+        let x = x_of(f64::from(channels::channel_freq(band, chan))).saturating_sub(text.len() as u16 / 2);
+        if x < free_from || x + text.len() as u16 > axis_row.x + axis_row.width {
+            continue;
+        }
+        buf.set_string(x, axis_row.y, &text, palette.hint);
+        free_from = x + text.len() as u16 + 1;
+    }
+
+    let advice: Vec<String> = channels::recommend(band, &app.access_points)
+        .iter()
+        .map(|rec| {
+            let dfs = if channels::is_dfs(band, rec.chan) { " DFS" } else { "" };
+            let crowd = match rec.neighbours {
+                0 => "none".to_string(),
+                1 => "1 AP".to_string(),
+                n => format!("{} APs", n),
+            };
+            format!("{}{} ({})", rec.chan, dfs, crowd)
+        })
+        .collect();
+    buf.set_stringn(
+        advice_row.x,
+        advice_row.y,
+        format!("Least crowded: {}", advice.join(", ")),
+        advice_row.width as usize,
+        Style::default(),
+    );
+}
+
+/// The history view covers everything but the status bar.
+fn history_area(screen: Rect) -> Rect {
+    Rect { height: screen.height.saturating_sub(1), ..screen }
+}
+
+/// Every access point on record, most recently seen first. Those seen for the
+/// first time lately are marked new; those missing from the current scan are
+/// dimmed.
+fn render_history(f: &mut Frame, area: Rect, app: &App) {
+    let palette = theme::palette();
+    let now = unix_now();
+    let here = |s: &history::Sighting| app.access_points.iter().any(|ap| ap.bssid == s.bssid);
+
+    // Marker, BSSID, best signal, first seen and last seen around the SSID;
+    // first seen, then the BSSID give way on narrow terminals
+    let width = area.width.saturating_sub(2) as usize;
+    let mut show_first = true;
+    let mut show_bssid = true;
+    let fixed = |first: bool, bssid: bool| 5 + 1 + 5 + 8 + if first { 17 } else { 0 } + if bssid { 18 } else { 0 };
+    if width < fixed(true, true) + MIN_SSID_WIDTH {
+        show_first = false;
+    }
+    if width < fixed(false, true) + MIN_SSID_WIDTH {
+        show_bssid = false;
+    }
+    let ssid_width = width.saturating_sub(fixed(show_first, show_bssid)).max(1);
+
+    let items: Vec<ListItem> = app
+        .history
+        .iter()
+        .map(|s| {
+            let marker = if now.saturating_sub(s.first_seen) < history::NEW_FOR_SECS { "new" } else { "" };
+            let (ssid, _) = fit_width(&s.ssid.to_string(), ssid_width);
+            let mut line = format!("{:<4} {} ", marker, ssid);
+            if show_bssid {
+                line.push_str(&format!("{:<17} ", s.bssid));
+            }
+            line.push_str(&format!("{:>3}% ", s.best_signal));
+            if show_first {
+                // "2024-05-01T09:30:00Z" down to "2024-05-01 09:30"
+                let first = utc_timestamp(s.first_seen).replacen('T', " ", 1);
+                line.push_str(&format!("{} ", first.get(..16).unwrap_or(&first)));
+            }
+            line.push_str(&history::ago(s.last_seen));
+            let style = if here(s) { Style::default() } else { palette.hint };
+            ListItem::new(line).style(style)
+        })
+        .collect();
+
+    let present = app.history.iter().filter(|s| here(s)).count();
+    let title = format!(" History: {} access points, {} in range ", app.history.len(), present);
+    if items.is_empty() {
+        let text = match history::path() {
+            _ if !config::get().history.enabled => "History is turned off (history.enabled)".to_string(),
+            Some(path) => format!("Nothing recorded yet in {}", path.display()),
+            None => "Nothing recorded yet".to_string(),
+        };
+        f.render_widget(Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(title)), area);
+        return;
+    }
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(palette.selected);
+    f.render_stateful_widget(list, area, &mut app.history_state.clone());
+}
+
+/// First row shown in the network list: where it was last time, scrolled
+/// just enough to keep the selection in view.
+fn list_offset(state: &ListState, list_area: Rect) -> usize {
+    let rows = (list_area.height.saturating_sub(2) as usize).max(1);
+    match state.selected() {
+        Some(idx) if idx < state.offset() => idx,
+        Some(idx) if idx >= state.offset() + rows => idx + 1 - rows,
+        _ => state.offset(),
+    }
+}
+
+/// Index of the network shown at `at`, if any.
+fn network_at(app: &App, screen: Rect, at: Position) -> Option<usize> {
+    let list_area = screen_layout(screen, app)[1];
+    let inner = Block::default().borders(Borders::ALL).inner(list_area);
+    if !inner.contains(at) {
+        return None;
+    }
+    let idx = app.list_state.offset() + (at.y - inner.y) as usize;
+    (idx < app.networks.len()).then_some(idx)
+}
+
+fn password_area(area: Rect) -> Rect {
+    centered_rect(60, 30, area)
+}
+
+/// Where the show/hide hint sits in the password popup, if it's bound.
+fn password_toggle_area(area: Rect) -> Option<Rect> {
+    let keymap = &config::get().keymap;
+    let key = keymap.key(Mode::Password, Action::TogglePassword)?;
+    let label = format!("{}: {}", key, Action::TogglePassword.label(Mode::Password));
+    let inner = Block::default().borders(Borders::ALL).inner(password_area(area));
+    // Fourth line, after the opening parenthesis
+    let hint = Rect::new(inner.x + 1, inner.y + 3, label.width() as u16, 1);
+    Some(hint.intersection(inner))
+}
+
+fn action_menu_area(area: Rect) -> Rect {
+    centered_rect(40, 25, area)
+}
+
+/// Index of the action menu entry shown at `at`, if any.
+fn action_at(app: &App, screen: Rect, at: Position) -> Option<usize> {
+    let inner = Block::default().borders(Borders::ALL).inner(action_menu_area(screen));
+    if !inner.contains(at) {
+        return None;
+    }
+    let idx = (at.y - inner.y) as usize;
+    (idx < app.action_items.len()).then_some(idx)
+}
+
+fn help_area(area: Rect) -> Rect {
+    // Use all of a narrow terminal, or the descriptions get cut off
+    centered_rect(if area.width < 60 { 100 } else { 70 }, 80, area)
+}
+
+fn help_max_scroll(area: Rect, mode: Mode) -> u16 {
+    let visible = help_area(area).height.saturating_sub(2);
+    (help_lines(mode).len() as u16).saturating_sub(visible)
+}
+
+/// Key bindings of `mode`, the icon and color legend, and where the settings
+/// come from.
+fn help_lines(mode: Mode) -> Vec<Line<'static>> {
+    let config = config::get();
+    let palette = theme::palette();
+    let icons = theme::icons();
+    let heading = |text: String| Line::styled(text, Style::default().add_modifier(Modifier::BOLD));
+
+    let mut lines = vec![heading(format!("Keys: {}", mode.title()))];
+    for (action, keys) in config.keymap.bindings(mode) {
+        let keys = if keys.is_empty() { "(unbound)".to_string() } else { keys.join(", ") };
+        lines.push(Line::from(format!("  {:<16} {}", keys, action.description(mode))));
+    }
+
+    lines.push(Line::default());
+    lines.push(heading("Icons".to_string()));
+    let steps = config.ui.signal_thresholds;
+    for (level, icon) in icons.signal.iter().enumerate() {
+        let low = if level == 0 { 0 } else { steps[level - 1].saturating_add(1) };
+        let high = steps.get(level).copied().unwrap_or(100);
+        // Equal thresholds leave a step that is never shown
+        if low <= high {
+            lines.push(Line::from(format!("  {:<6} Signal {}-{}%", icon, low, high)));
+        }
+    }
+    for (icon, meaning) in [
+        (icons.active.trim_end(), "Network in use"),
+        (icons.lock, "Secured network"),
+        (icons.busy, "Working"),
+        (icons.message, "Message, any key closes it"),
+    ] {
+        lines.push(Line::from(format!("  {:<6} {}", icon, meaning)));
+    }
+
+    lines.push(Line::default());
+    lines.push(heading("Colors".to_string()));
+    for (style, meaning) in [
+        (palette.active, "Network in use"),
+        (palette.selected, "Selected row"),
+        (palette.busy, "Working"),
+        (palette.message, "Message"),
+        (palette.error, "Error"),
+    ] {
+        lines.push(Line::from(vec![Span::raw("  "), Span::styled(meaning, style)]));
+    }
+
+    lines.push(Line::default());
+    lines.push(heading("Settings".to_string()));
+    lines.push(Line::from(format!("  Backend  {}", config.backend.name())));
+    lines.push(Line::from(match &config.path {
+        Some(path) => format!("  Config   {}", path.display()),
+        None => "  Config   none, using the built-in defaults".to_string(),
+    }));
+    lines
 }
 
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
@@ -526,96 +2172,367 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = match parse_cli_options() {
         Ok(opts) => opts,
         Err(e) => {
-            eprintln!("{}", e);
-            print_usage();
-            std::process::exit(2);
+            // Honour --json and --format json even though parsing did not finish
+            let args: Vec<String> = env::args().collect();
+            let json = args.iter().any(|a| a == "--json")
+                || args.windows(2).any(|pair| pair[0] == "--format" && pair[1] == "json");
+            if json {
+                output::emit_error(OutputFormat::Json, &e);
+            } else {
+                eprintln!("{}", e);
+                print_usage();
+            }
+            std::process::exit(e.exit_code());
         }
     };
 
-    let interfaces = get_wifi_interfaces();
+    if let Err(e) = config::load(cli.config.as_deref()) {
+        exit_with(cli.format, e);
+    }
+    theme::init(cli.theme, cli.icons);
+
+    // Lets hooks finish and held-back history get written before returning
+    let _exit = ExitGuard;
+
+    if cli.no_daemon {
+        daemon::disable();
+    }
+    if cli.notify {
+        notify::enable();
+    }
+    if cli.daemon {
+        if let Err(e) = daemon::serve() {
+            exit_with(cli.format, e);
+        }
+        return Ok(());
+    }
+
+    // The history is on disk, so it needs neither NetworkManager nor a device
+    if cli.history {
+        match run_history(&cli) {
+            Ok(report) => {
+                output::emit(cli.format, &report);
+                return Ok(());
+            }
+            Err(e) => exit_with(cli.format, e),
+        }
+    }
 
     if cli.status {
         match run_status(cli.interface.as_deref()) {
-            Ok(out) => {
-                println!("{}", out);
+            Ok(report) => {
+                output::emit(cli.format, &report);
                 return Ok(());
             }
-            Err(e) => {
-                eprintln!("{}", e);
-                std::process::exit(1);
+            Err(e) => exit_with(cli.format, e),
+        }
+    }
+
+    // A bar should show "unavailable" rather than fail when there's no device
+    if cli.bar {
+        if let Err(e) = bar::run(cli.bar_format, cli.interface.as_deref(), cli.watch) {
+            exit_with(cli.format, e);
+        }
+        return Ok(());
+    }
+
+    // Forgetting works on saved profiles, so it doesn't need a Wi-Fi device
+    if let Some(ssid) = &cli.forget {
+        match delete_connection(ssid) {
+            Ok(out) => {
+                output::emit(
+                    cli.format,
+                    &ActionReport {
+                        action: "forget",
+                        ssid: Some(ssid.to_string()),
+                        ok: true,
+                        message: Some(out).filter(|o| !o.is_empty()),
+                        ..Default::default()
+                    },
+                );
+                return Ok(());
             }
+            Err(e) => exit_with(cli.format, e),
         }
     }
 
+    let interfaces = get_wifi_interfaces().unwrap_or_else(|e| exit_with(cli.format, e));
+
+    if cli.secret_agent {
+        let agent = match agent::SecretAgent::register() {
+            Ok(agent) => agent,
+            Err(e) => exit_with(cli.format, e),
+        };
+        println!("Secret agent registered, waiting for NetworkManager requests...");
+        let current_interface = pick_default_interface(&interfaces).unwrap_or_default();
+
+        // Only take over the terminal while a request is being answered
+        while let Some(request) = agent.next() {
+            let mut app = App::new(interfaces.clone(), current_interface.clone());
+            app.begin_secret_prompt(request);
+
+            let mut terminal = setup_terminal()?;
+            let res = run_tui(&mut terminal, &mut app, Some(&agent), true);
+            restore_terminal(&mut terminal)?;
+            res?;
+        }
+        return Ok(());
+    }
+
     let selected_interface = if let Some(iface) = cli.interface.clone() {
         if interfaces.iter().any(|candidate| candidate == &iface) {
             iface
         } else {
-            eprintln!("Interface '{}' not found among Wi-Fi devices.", iface);
-            std::process::exit(1);
+            exit_with(cli.format, Error::InterfaceNotFound(iface));
         }
     } else {
         match pick_default_interface(&interfaces) {
             Some(iface) => iface,
-            None => {
-                eprintln!("No Wi-Fi interface found.");
-                std::process::exit(1);
-            }
+            None => exit_with(cli.format, Error::NoInterface),
         }
     };
 
+    if cli.list {
+        match run_list(&selected_interface, &cli) {
+            Ok(report) => {
+                output::emit(cli.format, &report);
+                return Ok(());
+            }
+            Err(e) => exit_with(cli.format, e),
+        }
+    }
+
+    if let Some(command) = &cli.dmenu {
+        let password_flag = cli.dmenu_password_flag.as_deref().or(config::get().dmenu.password_flag.as_deref());
+        let menu = dmenu::Menu::new(command, password_flag);
+        match dmenu::run(&menu, &selected_interface) {
+            Ok(Some(report)) => output::emit(cli.format, &report),
+            Ok(None) => {}
+            Err(e) => exit_with(cli.format, e),
+        }
+        return Ok(());
+    }
+
+    if let Some(ssid) = &cli.connect {
+        match run_connect(&selected_interface, ssid, &cli) {
+            Ok(report) => {
+                output::emit(cli.format, &report);
+                return Ok(());
+            }
+            Err(e) => exit_with(cli.format, e),
+        }
+    }
+
     if cli.rescan {
         match rescan_interface(&selected_interface) {
             Ok(out) => {
-                if !out.is_empty() {
-                    println!("{}", out);
-                } else {
-                    println!("rescan=ok interface={}", selected_interface);
-                }
+                output::emit(
+                    cli.format,
+                    &ActionReport {
+                        action: "rescan",
+                        interface: Some(selected_interface),
+                        ok: true,
+                        message: Some(out).filter(|o| !o.is_empty()),
+                        ..Default::default()
+                    },
+                );
                 return Ok(());
             }
-            Err(e) => {
-                eprintln!("{}", e);
-                std::process::exit(1);
-            }
+            Err(e) => exit_with(cli.format, e),
         }
     }
 
     if cli.disconnect {
         match disconnect_interface(&selected_interface) {
             Ok(out) => {
-                if !out.is_empty() {
-                    println!("{}", out);
-                } else {
-                    println!("disconnect=ok interface={}", selected_interface);
-                }
+                output::emit(
+                    cli.format,
+                    &ActionReport {
+                        action: "disconnect",
+                        interface: Some(selected_interface),
+                        ok: true,
+                        message: Some(out).filter(|o| !o.is_empty()),
+                        ..Default::default()
+                    },
+                );
                 return Ok(());
             }
-            Err(e) => {
-                eprintln!("{}", e);
-                std::process::exit(1);
-            }
+            Err(e) => exit_with(cli.format, e),
         }
     }
 
-    // Setup Terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let secret_agent = if cli.agent {
+        match agent::SecretAgent::register() {
+            Ok(agent) => Some(agent),
+            Err(e) => exit_with(cli.format, e),
+        }
+    } else {
+        None
+    };
 
+    if cli.notify {
+        notify::spawn_watcher();
+    }
+
+    // Before the terminal is taken over, so a bad path is reported plainly
+    let survey = match &cli.survey {
+        Some(path) => {
+            let interval = cli.survey_interval.unwrap_or(config::get().survey.interval);
+            match survey::Survey::open(path, Duration::from_secs(interval)) {
+                Ok(survey) => Some(survey),
+                Err(e) => exit_with(cli.format, e),
+            }
+        }
+        None => None,
+    };
+
+    let mut terminal = setup_terminal()?;
     let mut app = App::new(interfaces, selected_interface);
+    app.survey = survey;
     
     // Initial Scan
     app.mode = AppMode::Processing("Scanning...".to_string());
     terminal.draw(|f| ui(f, &app))?;
-    app.networks = get_networks(&app.current_interface);
-    if !app.networks.is_empty() { app.list_state.select(Some(0)); }
+    app.set_networks(get_networks(&app.current_interface));
     app.mode = AppMode::Browsing;
 
+    let res = run_tui(&mut terminal, &mut app, secret_agent.as_ref(), false);
+    restore_terminal(&mut terminal)?;
+    res?;
+
+    Ok(())
+}
+
+struct ExitGuard;
+
+impl Drop for ExitGuard {
+    fn drop(&mut self) {
+        hooks::finish();
+        let _ = history::flush();
+    }
+}
+
+/// Reports a fatal error in non-interactive mode and exits with its code.
+fn exit_with(format: OutputFormat, e: Error) -> ! {
+    output::emit_error(format, &e);
+    // `exit` skips the ExitGuard
+    hooks::finish();
+    let _ = history::flush();
+    std::process::exit(e.exit_code())
+}
+
+fn setup_terminal() -> io::Result<Terminal<CrosstermBackend<io::Stdout>>> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableBracketedPaste)?;
+    if config::get().ui.mouse {
+        execute!(stdout, EnableMouseCapture)?;
+    }
+    Terminal::new(CrosstermBackend::new(stdout))
+}
+
+fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), DisableMouseCapture, DisableBracketedPaste, LeaveAlternateScreen)?;
+    terminal.show_cursor()
+}
+
+/// Two clicks on the same row within this time count as a double-click.
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// The action a mouse event stands for in the current mode. Clicking a
+/// network or menu entry selects it first; `None` if there's nothing more to do.
+fn mouse_action(app: &mut App, screen: Rect, mouse: MouseEvent) -> Option<Action> {
+    let at = Position::new(mouse.column, mouse.row);
+    let click = mouse.kind == MouseEventKind::Down(MouseButton::Left);
+    let scrolls = matches!(
+        app.mode,
+        AppMode::Browsing | AppMode::Search | AppMode::ActionMenu | AppMode::Help(_) | AppMode::History
+    );
+    match mouse.kind {
+        MouseEventKind::ScrollDown if scrolls => return Some(Action::Next),
+        MouseEventKind::ScrollUp if scrolls => return Some(Action::Prev),
+        _ if !click => return None,
+        _ => {}
+    }
+    match app.mode {
+        AppMode::Browsing | AppMode::Search => {
+            let idx = network_at(app, screen, at)?;
+            app.list_state.select(Some(idx));
+            let double = app.last_click.is_some_and(|(time, last)| last == idx && time.elapsed() <= DOUBLE_CLICK);
+            // A third click starts over rather than connecting again
+            app.last_click = if double { None } else { Some((Instant::now(), idx)) };
+            (double && app.mode == AppMode::Browsing).then_some(Action::Connect)
+        }
+        AppMode::ActionMenu => match action_at(app, screen, at) {
+            Some(idx) => {
+                app.action_state.select(Some(idx));
+                Some(Action::Select)
+            }
+            None if !action_menu_area(screen).contains(at) => Some(Action::Cancel),
+            None => None,
+        },
+        AppMode::PasswordInput => {
+            password_toggle_area(screen).filter(|area| area.contains(at)).map(|_| Action::TogglePassword)
+        }
+        AppMode::Help(_) if !help_area(screen).contains(at) => Some(Action::Cancel),
+        // Like any key
+        AppMode::Message(_) => Some(Action::Cancel),
+        _ => None,
+    }
+}
+
+/// Runs the event loop. With `prompt_only`, returns as soon as the pending
+/// secret prompt has been answered and dismissed (used by `--secret-agent`).
+fn run_tui(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+    secret_agent: Option<&agent::SecretAgent>,
+    prompt_only: bool,
+) -> io::Result<()> {
+    let refresh_interval = Duration::from_secs(config::get().behavior.refresh_interval);
+    let mut last_refresh = Instant::now();
     loop {
-        terminal.draw(|f| ui(f, &app))?;
+        if !refresh_interval.is_zero() && last_refresh.elapsed() >= refresh_interval {
+            match app.mode {
+                AppMode::Browsing => app.set_networks(get_networks(&app.current_interface)),
+                AppMode::Channels => app.load_access_points(),
+                _ => {}
+            }
+            last_refresh = Instant::now();
+        }
+        if app.survey_scan.is_none() && app.survey.as_ref().is_some_and(|s| s.is_due()) {
+            app.start_survey_scan();
+        }
+        if let Err(e) = app.finish_survey_scan() {
+            app.survey = None;
+            app.mode = AppMode::Message(format!("Survey stopped: {}", e));
+        }
+        if let Some(agent) = secret_agent {
+            if app.pending_secret.is_none() && app.mode == AppMode::Browsing {
+                if let Some(request) = agent.try_next() {
+                    app.begin_secret_prompt(request);
+                }
+            }
+        }
+        if app.pending_secret.as_ref().is_some_and(|r| r.is_cancelled()) {
+            app.pending_secret = None;
+            app.mode = AppMode::Message("NetworkManager withdrew the password request".to_string());
+        }
+        if prompt_only && app.mode == AppMode::Browsing {
+            return Ok(());
+        }
+
+        // Scroll before drawing rather than while, so clicks are matched
+        // against the rows that are actually on screen
+        let size = terminal.size()?;
+        let screen = Rect::new(0, 0, size.width, size.height);
+        let offset = list_offset(&app.list_state, screen_layout(screen, app)[1]);
+        *app.list_state.offset_mut() = offset;
+        let offset = list_offset(&app.history_state, history_area(screen));
+        *app.history_state.offset_mut() = offset;
+
+        terminal.draw(|f| ui(f, app))?;
 
         // Special Handling for Processing State (non-event driven updates if needed, mostly blocking for now)
         if let AppMode::Processing(_msg) = &app.mode {
@@ -630,39 +2547,117 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
 
         if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind != KeyEventKind::Press { continue; }
-
+            let event = event::read()?;
+            if let Event::Paste(text) = &event {
+                if app.mode == AppMode::PasswordInput {
+                    app.input_buffer.insert_str(text);
+                    app.input_error = None;
+                }
+                if app.mode == AppMode::Waypoint {
+                    app.waypoint_input.insert_str(text);
+                }
+                if app.mode == AppMode::Search {
+                    app.search_input.insert_str(text);
+                    app.filter.query = app.search_input.text().to_string();
+                    app.apply_filter();
+                }
+            }
+            let keymap = &config::get().keymap;
+            // Clicks and the scroll wheel go through the same actions as keys
+            let input = match event {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    Some((app.mode.keymap_mode().and_then(|mode| keymap.action(mode, &key)), Some(key)))
+                }
+                Event::Mouse(mouse) => mouse_action(app, screen, mouse).map(|action| (Some(action), None)),
+                _ => None,
+            };
+            if let Some((action, key)) = input {
                 match app.mode {
                     AppMode::Browsing => {
-                        match key.code {
-                            KeyCode::Char('q') | KeyCode::Esc => break,
-                            KeyCode::Down | KeyCode::Char('j') => app.next_network(),
-                            KeyCode::Up | KeyCode::Char('k') => app.previous_network(),
-                            KeyCode::Char('r') => {
-                                app.mode = AppMode::Processing("Scanning...".to_string());
-                                terminal.draw(|f| ui(f, &app))?;
-                                let _ = rescan_interface(&app.current_interface);
-                                app.networks = get_networks(&app.current_interface);
-                                if !app.networks.is_empty() {
-                                    app.list_state.select(Some(0));
+                        match action {
+                            Some(Action::Quit) => break,
+                            Some(Action::Help) => {
+                                app.help_scroll = 0;
+                                app.mode = AppMode::Help(Mode::Browse);
+                            }
+                            Some(Action::Search) => {
+                                app.search_input.clear();
+                                app.search_input.insert_str(&app.filter.query);
+                                app.mode = AppMode::Search;
+                            }
+                            Some(Action::NextMatch) if !app.filter.query.is_empty() => app.next_network(),
+                            Some(Action::PrevMatch) if !app.filter.query.is_empty() => app.previous_network(),
+                            Some(Action::FilterOpen) => {
+                                app.filter.open_only = !app.filter.open_only;
+                                app.apply_filter();
+                            }
+                            Some(Action::FilterSaved) => {
+                                app.filter.saved_only = !app.filter.saved_only;
+                                app.apply_filter();
+                            }
+                            Some(Action::Filter5Ghz) => {
+                                app.filter.band_5ghz_only = !app.filter.band_5ghz_only;
+                                app.apply_filter();
+                            }
+                            Some(Action::FilterSignal) => {
+                                app.filter.cycle_min_signal();
+                                app.apply_filter();
+                            }
+                            Some(Action::ClearFilter) => {
+                                app.filter.clear();
+                                app.apply_filter();
+                            }
+                            Some(Action::CycleSort) => {
+                                app.sort = app.sort.next();
+                                app.apply_filter();
+                            }
+                            Some(Action::ReverseSort) => {
+                                app.sort_reverse = !app.sort_reverse;
+                                app.apply_filter();
+                            }
+                            Some(Action::Details) => app.show_details = !app.show_details,
+                            Some(Action::Waypoint) if app.survey.is_some() => {
+                                app.waypoint_input.clear();
+                                app.mode = AppMode::Waypoint;
+                            }
+                            Some(Action::Waypoint) => {
+                                app.mode = AppMode::Message("Not recording; start with --survey <file>".to_string());
+                            }
+                            Some(Action::History) => {
+                                app.mode = AppMode::Processing("Reading history...".to_string());
+                                terminal.draw(|f| ui(f, app))?;
+                                if let Err(e) = app.open_history() {
+                                    app.mode = AppMode::Message(format!("History unavailable: {}", e));
                                 }
-                                app.mode = AppMode::Browsing;
                             }
-                            KeyCode::Char('i') => {
+                            Some(Action::Channels) => {
+                                app.mode = AppMode::Processing("Reading channels...".to_string());
+                                terminal.draw(|f| ui(f, app))?;
+                                app.open_channels();
+                            }
+                            Some(Action::Next) => app.next_network(),
+                            Some(Action::Prev) => app.previous_network(),
+                            Some(Action::Rescan) => {
+                                app.mode = AppMode::Processing("Scanning...".to_string());
+                                terminal.draw(|f| ui(f, app))?;
+                                let res = rescan_interface(&app.current_interface);
+                                app.set_networks(get_networks(&app.current_interface));
+                                app.mode = match res {
+                                    Ok(_) => AppMode::Browsing,
+                                    Err(e) => AppMode::Message(format!("Rescan failed: {}", e)),
+                                };
+                            }
+                            Some(Action::CycleInterface) => {
                                 app.cycle_interface();
                                 app.mode = AppMode::Processing(format!(
                                     "Switching to {}...",
                                     app.current_interface
                                 ));
-                                terminal.draw(|f| ui(f, &app))?;
-                                app.networks = get_networks(&app.current_interface);
-                                if !app.networks.is_empty() {
-                                    app.list_state.select(Some(0));
-                                }
+                                terminal.draw(|f| ui(f, app))?;
+                                app.set_networks(get_networks(&app.current_interface));
                                 app.mode = AppMode::Browsing;
                             }
-                            KeyCode::Enter => {
+                            Some(Action::Connect) => {
                                 if let Some(idx) = app.list_state.selected() {
                                     let net = app.networks[idx].clone();
                                     app.target_ssid = net.ssid.clone();
@@ -675,7 +2670,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                     } else {
                                         // Try connecting
                                         app.mode = AppMode::Processing(format!("Connecting to {}...", net.ssid));
-                                        terminal.draw(|f| ui(f, &app))?;
+                                        terminal.draw(|f| ui(f, app))?;
                                         
                                         // Try passwordless/saved first
                                         let res = connect_network(
@@ -688,17 +2683,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                         match res {
                                             Ok(_) => {
                                                 app.mode = AppMode::Message(format!("Connected to {}", net.ssid));
-                                                app.networks = get_networks(&app.current_interface); // Refresh status
+                                                app.set_networks(get_networks(&app.current_interface)); // Refresh status
                                             },
-                                            Err(_) => {
-                                                // Failed. Need password?
-                                                if !net.security.is_empty() {
-                                                    app.mode = AppMode::PasswordInput;
-                                                    app.input_buffer.clear();
-                                                    app.show_password = false;
-                                                } else {
-                                                    app.mode = AppMode::Message(format!("Failed to connect to {}", net.ssid));
-                                                }
+                                            // Only a secrets failure means we should ask for a password
+                                            Err(Error::Connect { kind: ConnectError::BadSecrets, .. })
+                                                if !net.security.is_empty() =>
+                                            {
+                                                app.mode = AppMode::PasswordInput;
+                                                app.input_buffer.clear();
+                                                app.input_error = None;
+                                                app.show_password = false;
+                                            }
+                                            Err(e) => {
+                                                app.mode = AppMode::Message(format!("Failed to connect to {}: {}", net.ssid, e));
                                             }
                                         }
                                     }
@@ -708,51 +2705,86 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         }
                     }
                     AppMode::PasswordInput => {
-                        match key.code {
-                            KeyCode::Esc => app.mode = AppMode::Browsing,
-                            KeyCode::Tab => app.show_password = !app.show_password,
-                            KeyCode::Enter => {
+                        match action {
+                            Some(Action::Cancel) => {
+                                if let Some(request) = app.pending_secret.take() {
+                                    request.respond(None);
+                                }
+                                app.mode = AppMode::Browsing;
+                            }
+                            Some(Action::TogglePassword) => app.show_password = !app.show_password,
+                            // Invalid input stays in the prompt; the error is already shown inline
+                            Some(Action::Submit)
+                                if validate_password(&app.target_security, app.input_buffer.text()).is_err() => {}
+                            Some(Action::Submit) if app.pending_secret.is_some() => {
+                                if let Some(request) = app.pending_secret.take() {
+                                    request.respond(Some(app.input_buffer.text().to_string()));
+                                    app.mode = AppMode::Message(format!(
+                                        "Sent password for {} to NetworkManager",
+                                        request.ssid
+                                    ));
+                                }
+                                app.input_buffer.clear();
+                            }
+                            Some(Action::Submit) => {
                                 app.mode = AppMode::Processing("Verifying Password...".to_string());
-                                terminal.draw(|f| ui(f, &app))?;
+                                terminal.draw(|f| ui(f, app))?;
                                 
                                 let res = connect_network(
                                     &app.target_ssid,
                                     &app.target_bssid,
-                                    &app.input_buffer,
+                                    app.input_buffer.text(),
                                     &app.target_security,
                                     &app.current_interface,
                                 );
                                 match res {
                                     Ok(_) => {
                                         app.mode = AppMode::Message("Success!".to_string());
-                                        app.networks = get_networks(&app.current_interface);
+                                        app.set_networks(get_networks(&app.current_interface));
                                     },
+                                    Err(Error::Connect { kind: ConnectError::BadSecrets, .. }) => {
+                                        app.mode = AppMode::PasswordInput;
+                                        app.input_error = Some(ConnectError::BadSecrets.to_string());
+                                    }
                                     Err(e) => app.mode = AppMode::Message(format!("Error: {}", e)),
                                 }
                             }
-                            KeyCode::Backspace => { app.input_buffer.pop(); }
-                            KeyCode::Char(c) => { app.input_buffer.push(c); }
-                            _ => {} 
+                            _ => {
+                                if key.is_some_and(|key| app.input_buffer.handle_key(&key)) {
+                                    app.input_error = None;
+                                }
+                            }
                         }
                     }
                     AppMode::ActionMenu => {
-                         match key.code {
-                            KeyCode::Esc => app.mode = AppMode::Browsing,
-                            KeyCode::Up | KeyCode::Char('k') => app.previous_action(),
-                            KeyCode::Down | KeyCode::Char('j') => app.next_action(),
-                            KeyCode::Enter => {
+                         match action {
+                            Some(Action::Cancel) => app.mode = AppMode::Browsing,
+                            Some(Action::Help) => {
+                                app.help_scroll = 0;
+                                app.mode = AppMode::Help(Mode::ActionMenu);
+                            }
+                            Some(Action::Prev) => app.previous_action(),
+                            Some(Action::Next) => app.next_action(),
+                            Some(Action::Select) => {
                                 if let Some(idx) = app.action_state.selected() {
                                     let action = app.action_items[idx];
                                     match action {
                                         "Disconnect" => {
-                                            let _ = disconnect_interface(&app.current_interface);
-                                            app.mode = AppMode::Message("Disconnected".to_string());
-                                            app.networks = get_networks(&app.current_interface);
+                                            app.mode = match disconnect_interface(&app.current_interface) {
+                                                Ok(_) => AppMode::Message("Disconnected".to_string()),
+                                                Err(e) => AppMode::Message(format!("Disconnect failed: {}", e)),
+                                            };
+                                            app.set_networks(get_networks(&app.current_interface));
                                         },
+                                        "Forget" if config::get().behavior.confirm_forget => {
+                                            app.mode = AppMode::ConfirmForget;
+                                        }
                                         "Forget" => {
-                                            let _ = delete_connection(&app.target_ssid);
-                                            app.mode = AppMode::Message("Network Forgotten".to_string());
-                                            app.networks = get_networks(&app.current_interface);
+                                            app.mode = match delete_connection(&app.target_ssid) {
+                                                Ok(_) => AppMode::Message("Network Forgotten".to_string()),
+                                                Err(e) => AppMode::Message(format!("Forget failed: {}", e)),
+                                            };
+                                            app.set_networks(get_networks(&app.current_interface));
                                         },
                                         _ => app.mode = AppMode::Browsing,
                                     }
@@ -761,8 +2793,108 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                              _ => {} 
                          }
                     }
+                    AppMode::ConfirmForget => {
+                        app.mode = match action {
+                            Some(Action::Confirm) => match delete_connection(&app.target_ssid) {
+                                Ok(_) => AppMode::Message("Network Forgotten".to_string()),
+                                Err(e) => AppMode::Message(format!("Forget failed: {}", e)),
+                            },
+                            _ => AppMode::Browsing,
+                        };
+                        app.set_networks(get_networks(&app.current_interface));
+                    }
+                    AppMode::Search => match action {
+                        Some(Action::Submit) => app.mode = AppMode::Browsing,
+                        Some(Action::Cancel) => {
+                            app.filter.query.clear();
+                            app.apply_filter();
+                            app.mode = AppMode::Browsing;
+                        }
+                        Some(Action::Next) => app.next_network(),
+                        Some(Action::Prev) => app.previous_network(),
+                        _ => {
+                            if key.is_some_and(|key| app.search_input.handle_key(&key)) {
+                                app.filter.query = app.search_input.text().to_string();
+                                app.apply_filter();
+                            }
+                        }
+                    },
+                    AppMode::Waypoint => match action {
+                        Some(Action::Submit) => {
+                            let name = app.waypoint_input.text().to_string();
+                            let res = match app.survey.as_mut() {
+                                Some(survey) => survey.waypoint(&app.current_interface, &name),
+                                None => Ok(()),
+                            };
+                            app.mode = match res {
+                                Ok(()) => AppMode::Browsing,
+                                Err(e) => AppMode::Message(format!("Waypoint not saved: {}", e)),
+                            };
+                        }
+                        Some(Action::Cancel) => app.mode = AppMode::Browsing,
+                        _ => {
+                            if let Some(key) = key {
+                                app.waypoint_input.handle_key(&key);
+                            }
+                        }
+                    },
+                    AppMode::History => {
+                        let page = history_area(screen).height.saturating_sub(2).max(1) as isize;
+                        match action {
+                            Some(Action::Next) => app.move_history(1),
+                            Some(Action::Prev) => app.move_history(-1),
+                            Some(Action::PageDown) => app.move_history(page),
+                            Some(Action::PageUp) => app.move_history(-page),
+                            Some(Action::Help) => {
+                                app.help_scroll = 0;
+                                app.mode = AppMode::Help(Mode::History);
+                            }
+                            Some(Action::Cancel) => app.mode = AppMode::Browsing,
+                            _ => {}
+                        }
+                    }
+                    AppMode::Channels => match action {
+                        Some(Action::Next) => app.cycle_band(1),
+                        Some(Action::Prev) => app.cycle_band(channels::BANDS.len() - 1),
+                        Some(Action::Rescan) => {
+                            app.mode = AppMode::Processing("Scanning...".to_string());
+                            terminal.draw(|f| ui(f, app))?;
+                            let res = rescan_interface(&app.current_interface);
+                            app.load_access_points();
+                            app.set_networks(get_networks(&app.current_interface));
+                            app.mode = match res {
+                                Ok(_) => AppMode::Channels,
+                                Err(e) => AppMode::Message(format!("Rescan failed: {}", e)),
+                            };
+                        }
+                        Some(Action::Help) => {
+                            app.help_scroll = 0;
+                            app.mode = AppMode::Help(Mode::Channels);
+                        }
+                        Some(Action::Cancel) => app.mode = AppMode::Browsing,
+                        _ => {}
+                    },
+                    AppMode::Help(from) => {
+                        let max_scroll = help_max_scroll(screen, from);
+                        let page = help_area(screen).height.saturating_sub(2);
+                        match action {
+                            Some(Action::Next) => app.help_scroll = (app.help_scroll + 1).min(max_scroll),
+                            Some(Action::Prev) => app.help_scroll = app.help_scroll.saturating_sub(1),
+                            Some(Action::PageDown) => app.help_scroll = (app.help_scroll + page).min(max_scroll),
+                            Some(Action::PageUp) => app.help_scroll = app.help_scroll.saturating_sub(page),
+                            Some(Action::Cancel) => {
+                                app.mode = match from {
+                                    Mode::ActionMenu => AppMode::ActionMenu,
+                                    Mode::Channels => AppMode::Channels,
+                                    Mode::History => AppMode::History,
+                                    _ => AppMode::Browsing,
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
                     AppMode::Message(_) => {
-                        // Any key returns to browsing
+                        // Any key or click returns to browsing
                         app.mode = AppMode::Browsing;
                     }
                     _ => {} 
@@ -771,10 +2903,157 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    // Restore Terminal
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
-
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wpa_passphrase_lengths() {
+        assert!(validate_password("WPA2", "1234567").is_err());
+        assert!(validate_password("WPA2", "12345678").is_ok());
+        assert!(validate_password("WPA1 WPA2", &"x".repeat(63)).is_ok());
+        assert!(validate_password("WPA2", &"x".repeat(64)).is_err());
+        assert!(validate_password("WPA2", &"0".repeat(65)).is_err());
+        assert!(validate_password("WPA2", &format!("{}0", "aF0".repeat(21))).is_ok());
+        assert!(validate_password("WPA2", "pässwörd").is_err());
+        // WPA3 transition networks still take a WPA2 passphrase
+        assert!(validate_password("WPA2 WPA3", "short").is_err());
+    }
+
+    #[test]
+    fn sae_and_enterprise_only_need_a_password() {
+        assert!(validate_password("WPA3", "").is_err());
+        assert!(validate_password("WPA3", "pw").is_ok());
+        assert!(validate_password("WPA2 802.1X", "").is_err());
+        assert!(validate_password("WPA2 802.1X", "pässwörd").is_ok());
+    }
+
+    #[test]
+    fn wep_key_lengths() {
+        for key in ["abcde", "abcdefghijklm", "0123456789", "0123456789abcdef0123456789"] {
+            assert!(validate_password("WEP", key).is_ok(), "{key}");
+        }
+        for key in ["abcd", "abcdef", "012345678g", "0123456789abcdef012345678", "äbcde"] {
+            assert!(validate_password("WEP", key).is_err(), "{key}");
+        }
+    }
+
+    #[test]
+    fn connect_args_never_carry_the_password() {
+        let secret = "hunter2-secret";
+        for ask in [true, false] {
+            for bssid in ["", "AA:BB:CC:DD:EE:01"] {
+                let args = connect_args("Home", "wlan0", bssid, "Home", ask);
+                assert!(args.iter().all(|a| !a.contains(secret) && *a != "password"), "{args:?}");
+                assert_eq!(args.first() == Some(&"--ask"), ask);
+            }
+        }
+    }
+
+    struct MockAccessPoint {
+        last_seen: i32,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.NetworkManager.AccessPoint")]
+    impl MockAccessPoint {
+        #[zbus(property)]
+        fn last_seen(&self) -> i32 {
+            self.last_seen
+        }
+    }
+
+    #[test]
+    fn last_seen_comes_from_one_object_manager_call() {
+        use std::os::unix::net::UnixStream;
+        use zbus::blocking::connection::Builder;
+
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            Builder::async_io_unix_stream(server)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at("/org/freedesktop", zbus::fdo::ObjectManager)
+                .unwrap()
+                .serve_at("/org/freedesktop/NetworkManager/AccessPoint/1", MockAccessPoint { last_seen: 0 })
+                .unwrap()
+                .serve_at("/org/freedesktop/NetworkManager/AccessPoint/2", MockAccessPoint { last_seen: -1 })
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let client = Builder::async_io_unix_stream(client).p2p().build().unwrap();
+        let _server = server.join().unwrap();
+
+        let mut networks = parse_networks(
+            "*:Home:AA\\:BB\\:CC\\:DD\\:EE\\:01:WPA2:82:2437 MHz:6:/org/freedesktop/NetworkManager/AccessPoint/1\n\
+             :Gone:AA\\:BB\\:CC\\:DD\\:EE\\:02::40:2412 MHz:1:/org/freedesktop/NetworkManager/AccessPoint/2\n\
+             :Unknown:AA\\:BB\\:CC\\:DD\\:EE\\:03::30:2412 MHz:1:/org/freedesktop/NetworkManager/AccessPoint/3\n",
+        );
+        fill_last_seen(&client, &mut networks);
+        let uptime: u64 = fs::read_to_string("/proc/uptime").unwrap().split('.').next().unwrap().parse().unwrap();
+        // Seen at boot, so as long ago as the uptime
+        assert!(networks[0].last_seen.is_some_and(|ago| ago.abs_diff(uptime) <= 1), "{:?}", networks[0]);
+        assert_eq!(networks[1].last_seen, None);
+        assert_eq!(networks[2].last_seen, None);
+    }
+
+    #[test]
+    fn fit_width_cuts_wide_and_combining_text_at_column_boundaries() {
+        let cases = [
+            // text, width, fitted, chars kept
+            ("Home", 6, "Home  ", 4),
+            ("日本語ネットワーク", 10, "日本語... ", 3),
+            ("日本語ネットワーク", 18, "日本語ネットワーク", 9),
+            ("🙂🙂🙂🙂", 6, "🙂... ", 1),
+            ("Cafe\u{301} Paris", 10, "Cafe\u{301} Paris", 11),
+            ("Cafe\u{301} Paris Nord", 10, "Cafe\u{301} Pa...", 8),
+            ("日本", 2, "日", 1),
+            ("日本", 1, " ", 0),
+        ];
+        for (text, width, fitted, kept) in cases {
+            assert_eq!(fit_width(text, width), (fitted.to_string(), kept), "{text:?} in {width}");
+        }
+    }
+
+    #[test]
+    fn fit_width_always_fills_the_width_exactly() {
+        let names = ["", "a", "日本語ネットワーク", "👨‍👩‍👧 Family", "e\u{301}\u{301}\u{301}", "Caf\\xE9\\x09Bar", "한국어 와이파이"];
+        for name in names {
+            for width in 0..24 {
+                let (fitted, kept) = fit_width(name, width);
+                assert_eq!(fitted.width(), width, "{name:?} in {width}");
+                assert!(kept <= name.chars().count());
+            }
+        }
+    }
+
+    #[test]
+    fn list_layout_measures_ssids_in_columns() {
+        let wide = parse_networks(&format!(
+            "*:{}:AA\\:BB\\:CC\\:DD\\:EE\\:01:WPA2:82:2437 MHz:6:/ap/1",
+            "日本語".repeat(6)
+        ));
+        // 36 columns although only 18 chars
+        let (columns, ssid_width) = list_layout(200, &wide);
+        assert_eq!(ssid_width, 36);
+        assert_eq!(columns, config::get().ui.columns);
+
+        // On a narrow terminal the SSID keeps at least MIN_SSID_WIDTH columns
+        for width in 0..60 {
+            let (columns, ssid_width) = list_layout(width, &wide);
+            if width >= MIN_SSID_WIDTH {
+                assert!(ssid_width >= MIN_SSID_WIDTH, "{width}: {columns:?} {ssid_width}");
+            }
+            assert!(ssid_width <= width);
+        }
+    }
+
+    #[test]
+    fn open_networks_take_anything() {
+        assert!(validate_password("", "").is_ok());
+    }
+}
@@ -0,0 +1,288 @@
+// Desktop notifications.
+//
+// With `--notify`, connection events are also shown through the freedesktop
+// notification service on the session bus. Notifications replace each other
+// instead of stacking, so a flapping connection produces one bubble rather
+// than dozens. Connects and disconnects are always shown; failures and
+// signal warnings are dropped when the same one was shown recently.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use zbus::blocking::Connection;
+use zbus::zvariant::Value;
+
+use crate::{bar, get_wifi_interfaces, hooks, Network};
+
+const NOTIFICATIONS_SERVICE: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+const APP_NAME: &str = "wifi_menu";
+const APP_ICON: &str = "network-wireless";
+
+// The same failure or warning again within this window is dropped
+const REPEAT_WINDOW: Duration = Duration::from_secs(30);
+// Warn below WEAK_SIGNAL, and again only after recovering above WEAK_SIGNAL_CLEAR
+const WEAK_SIGNAL: u8 = 30;
+const WEAK_SIGNAL_CLEAR: u8 = 40;
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy)]
+enum Urgency {
+    Low = 0,
+    Normal = 1,
+}
+
+struct Notifier {
+    /// `None` without a session bus; there's nobody to tell then
+    connection: Option<Connection>,
+    /// Id of the last notification, so the next one replaces it
+    last_id: u32,
+    /// Summary and body of the last notification
+    last: Option<(String, String)>,
+    /// When each failure or warning was last shown
+    shown: BTreeMap<(String, String), Instant>,
+    /// Interfaces already warned about a weak signal
+    weak: BTreeSet<String>,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static NOTIFIER: OnceLock<Mutex<Notifier>> = OnceLock::new();
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+fn with_notifier(f: impl FnOnce(&mut Notifier)) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let notifier = NOTIFIER.get_or_init(|| Mutex::new(Notifier::new(Connection::session().ok())));
+    f(&mut notifier.lock().unwrap_or_else(|e| e.into_inner()));
+}
+
+pub fn connected(ssid: &str) {
+    with_notifier(|n| n.connected(ssid));
+}
+
+pub fn disconnected(ssid: &str) {
+    with_notifier(|n| n.disconnected(ssid));
+}
+
+pub fn failed(ssid: &str, reason: &str) {
+    with_notifier(|n| n.failed(ssid, reason));
+}
+
+/// Warns once when the active network's signal drops below `WEAK_SIGNAL`.
+pub fn signal(interface: &str, active: Option<&Network>) {
+    with_notifier(|n| n.signal(interface, active));
+}
+
+impl Notifier {
+    fn new(connection: Option<Connection>) -> Self {
+        Self {
+            connection,
+            last_id: 0,
+            last: None,
+            shown: BTreeMap::new(),
+            weak: BTreeSet::new(),
+        }
+    }
+
+    fn connected(&mut self, ssid: &str) {
+        self.transition(&format!("Connected to {}", ssid));
+    }
+
+    fn disconnected(&mut self, ssid: &str) {
+        let summary = match ssid {
+            "" => "Wi-Fi disconnected".to_string(),
+            ssid => format!("Disconnected from {}", ssid),
+        };
+        self.transition(&summary);
+    }
+
+    fn failed(&mut self, ssid: &str, reason: &str) {
+        self.throttled(&format!("Could not connect to {}", ssid), reason, Urgency::Normal);
+    }
+
+    fn signal(&mut self, interface: &str, active: Option<&Network>) {
+        let weak = match active {
+            Some(net) if net.signal < WEAK_SIGNAL => self.weak.insert(interface.to_string()),
+            Some(net) if net.signal < WEAK_SIGNAL_CLEAR => false,
+            _ => {
+                self.weak.remove(interface);
+                false
+            }
+        };
+        if let Some(net) = active.filter(|_| weak) {
+            self.throttled(
+                &format!("Weak Wi-Fi signal on {}", net.ssid),
+                &format!("Signal strength is {}%", net.signal),
+                Urgency::Low,
+            );
+        }
+    }
+
+    /// Shows a connect or disconnect unless it is already on screen. The last
+    /// of a burst of these is the state the user needs to see.
+    fn transition(&mut self, summary: &str) {
+        if self.last.as_ref().is_some_and(|(s, b)| s == summary && b.is_empty()) {
+            return;
+        }
+        self.show(summary, "", Urgency::Normal);
+    }
+
+    /// Shows a failure or warning unless the same one was shown within `REPEAT_WINDOW`.
+    fn throttled(&mut self, summary: &str, body: &str, urgency: Urgency) {
+        let now = Instant::now();
+        self.shown.retain(|_, at| now.duration_since(*at) < REPEAT_WINDOW);
+        let key = (summary.to_string(), body.to_string());
+        if self.shown.contains_key(&key) {
+            return;
+        }
+        self.shown.insert(key, now);
+        self.show(summary, body, urgency);
+    }
+
+    fn show(&mut self, summary: &str, body: &str, urgency: Urgency) {
+        self.last = Some((summary.to_string(), body.to_string()));
+        // Notifications are best effort
+        let Some(connection) = &self.connection else { return };
+        let hints = HashMap::from([("urgency", Value::U8(urgency as u8))]);
+        let reply = connection.call_method(
+            Some(NOTIFICATIONS_SERVICE),
+            NOTIFICATIONS_PATH,
+            Some(NOTIFICATIONS_SERVICE),
+            "Notify",
+            &(APP_NAME, self.last_id, APP_ICON, summary, body, Vec::<&str>::new(), hints, -1i32),
+        );
+        if let Ok(id) = reply.and_then(|msg| msg.body().deserialize::<u32>()) {
+            self.last_id = id;
+        }
+    }
+}
+
+/// Polls every Wi-Fi device in the background so the TUI notices changes
+/// while it is waiting for input.
+pub fn spawn_watcher() {
+    thread::spawn(|| loop {
+        for iface in get_wifi_interfaces().unwrap_or_default() {
+            if let Ok(active) = bar::active_network(&iface) {
+                hooks::observe(&iface, active.as_ref());
+            }
+        }
+        thread::sleep(WATCH_INTERVAL);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_networks;
+    use std::os::unix::net::UnixStream;
+    use std::sync::Arc;
+    use zbus::blocking::connection::Builder;
+    use zbus::zvariant::OwnedValue;
+
+    /// replaces_id, summary, body and urgency of one Notify call
+    type Call = (u32, String, String, u8);
+
+    struct MockServer {
+        calls: Arc<Mutex<Vec<Call>>>,
+        next_id: u32,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl MockServer {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &mut self,
+            _app_name: String,
+            replaces_id: u32,
+            _app_icon: String,
+            summary: String,
+            body: String,
+            _actions: Vec<String>,
+            hints: HashMap<String, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let urgency = hints.get("urgency").and_then(|v| u8::try_from(v).ok()).unwrap_or(u8::MAX);
+            self.calls.lock().unwrap().push((replaces_id, summary, body, urgency));
+            self.next_id += 1;
+            self.next_id
+        }
+    }
+
+    /// Serves the mock on one end of a socket pair and returns a client
+    /// connection to it.
+    fn serve_mock(calls: &Arc<Mutex<Vec<Call>>>) -> (Connection, Connection) {
+        let (client, server) = UnixStream::pair().unwrap();
+        let mock = MockServer { calls: Arc::clone(calls), next_id: 41 };
+        // The handshake needs both ends at once
+        let server = thread::spawn(move || {
+            Builder::async_io_unix_stream(server)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at(NOTIFICATIONS_PATH, mock)
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let client = Builder::async_io_unix_stream(client).p2p().build().unwrap();
+        (client, server.join().unwrap())
+    }
+
+    fn weak_network(signal: u8) -> Vec<Network> {
+        parse_networks(&format!("*:Cafe:AA\\:BB\\:CC\\:DD\\:EE\\:02:WPA2:{}:2412 MHz:1:/ap/2", signal))
+    }
+
+    #[test]
+    fn notifications_replace_each_other() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let (client, _server) = serve_mock(&calls);
+        let mut notifier = Notifier::new(Some(client));
+
+        notifier.connected("Home Net");
+        // Already on screen
+        notifier.connected("Home Net");
+        notifier.disconnected("Home Net");
+        let weak = weak_network(20);
+        notifier.signal("wlan0", weak.first());
+        // Warned once until the signal recovers
+        notifier.signal("wlan0", weak.first());
+
+        let text = |s: &str| s.to_string();
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                (0, text("Connected to Home Net"), text(""), Urgency::Normal as u8),
+                (42, text("Disconnected from Home Net"), text(""), Urgency::Normal as u8),
+                (43, text("Weak Wi-Fi signal on Cafe"), text("Signal strength is 20%"), Urgency::Low as u8),
+            ]
+        );
+    }
+
+    #[test]
+    fn flapping_shows_every_transition_but_throttles_repeats() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let (client, _server) = serve_mock(&calls);
+        let mut notifier = Notifier::new(Some(client));
+
+        for _ in 0..4 {
+            notifier.connected("Home Net");
+            notifier.failed("Cafe", "Wrong password");
+            notifier.disconnected("Home Net");
+        }
+
+        let calls = calls.lock().unwrap();
+        let summaries: Vec<&str> = calls.iter().map(|call| call.1.as_str()).collect();
+        assert_eq!(summaries.iter().filter(|s| **s == "Could not connect to Cafe").count(), 1);
+        assert_eq!(summaries.iter().filter(|s| **s == "Connected to Home Net").count(), 4);
+        // What is left on screen is the real state
+        assert_eq!(summaries.last(), Some(&"Disconnected from Home Net"));
+        assert_eq!(summaries.len(), 9);
+    }
+}
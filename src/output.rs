@@ -0,0 +1,257 @@
+// Output formats for the non-interactive commands.
+//
+// Every command produces a report that can be printed as the historical
+// `key=value` text, as TSV, or as a single-line JSON object. The JSON shapes
+// are documented in the README and should only ever grow new fields.
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::error::Error;
+use crate::ssid::Ssid;
+use crate::Network;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Tsv,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        match value {
+            "text" => Ok(OutputFormat::Text),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(Error::Usage(format!(
+                "Unknown format '{}', expected text, tsv or json",
+                value
+            ))),
+        }
+    }
+}
+
+pub trait Report: Serialize {
+    fn text(&self) -> String;
+    fn tsv(&self) -> String;
+}
+
+pub fn emit<R: Report>(format: OutputFormat, report: &R) {
+    let out = match format {
+        OutputFormat::Text => report.text(),
+        OutputFormat::Tsv => report.tsv(),
+        OutputFormat::Json => match serde_json::to_string(report) {
+            Ok(json) => json,
+            Err(e) => return eprintln!("{}", e),
+        },
+    };
+    // An empty list prints nothing rather than a blank line
+    if !out.is_empty() {
+        println!("{}", out);
+    }
+}
+
+/// Prints an error. In JSON mode it goes to stdout like any other result, so
+/// scripts only have to read one stream; otherwise it goes to stderr.
+pub fn emit_error(format: OutputFormat, e: &Error) {
+    if format != OutputFormat::Json {
+        eprintln!("{}", e);
+        return;
+    }
+
+    println!("{}", json!({ "error": e.to_json() }));
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct DeviceStatus {
+    pub interface: String,
+    pub state: String,
+    pub connection: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct StatusReport {
+    pub devices: Vec<DeviceStatus>,
+}
+
+impl Report for StatusReport {
+    fn text(&self) -> String {
+        if self.devices.is_empty() {
+            return "interface=none state=unavailable connection=--".to_string();
+        }
+        self.devices
+            .iter()
+            .map(|d| {
+                format!(
+                    "interface={} state={} connection={}",
+                    d.interface,
+                    d.state,
+                    d.connection.as_deref().unwrap_or("--")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn tsv(&self) -> String {
+        self.devices
+            .iter()
+            .map(|d| {
+                format!(
+                    "{}\t{}\t{}",
+                    d.interface,
+                    d.state,
+                    d.connection.as_deref().unwrap_or("")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Result of a one-shot action such as `--rescan`, `--connect` or `--forget`.
+#[derive(Default, Serialize)]
+pub struct ActionReport {
+    pub action: &'static str,
+    /// Not set for `--forget`, which works on profiles rather than devices
+    pub interface: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssid: Option<String>,
+    pub ok: bool,
+    /// Whatever nmcli printed, if anything
+    pub message: Option<String>,
+    /// Address reported once `--wait` saw the device come up
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip4: Option<String>,
+}
+
+impl Report for ActionReport {
+    fn text(&self) -> String {
+        let mut out = match &self.message {
+            Some(msg) => msg.clone(),
+            None => {
+                let mut line = format!("{}=ok", self.action);
+                if let Some(iface) = &self.interface {
+                    line.push_str(&format!(" interface={}", iface));
+                }
+                if let Some(ssid) = &self.ssid {
+                    line.push_str(&format!(" ssid={}", ssid));
+                }
+                line
+            }
+        };
+        if let Some(ip4) = &self.ip4 {
+            out.push_str(&format!("\nip4={}", ip4));
+        }
+        out
+    }
+
+    fn tsv(&self) -> String {
+        let mut row = format!("{}\t{}\tok", self.action, self.interface.as_deref().unwrap_or(""));
+        // Columns added for --connect/--forget go after the ones scripts
+        // already split --rescan/--disconnect rows on
+        if self.ssid.is_some() || self.ip4.is_some() {
+            row.push_str(&format!(
+                "\t{}\t{}",
+                self.ssid.as_deref().unwrap_or(""),
+                self.ip4.as_deref().unwrap_or("")
+            ));
+        }
+        row
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct NetworkList {
+    pub interface: String,
+    pub networks: Vec<Network>,
+}
+
+impl Report for NetworkList {
+    fn text(&self) -> String {
+        self.networks
+            .iter()
+            .map(|n| {
+                format!(
+                    "{} {:>3}% {:<10} {}",
+                    if n.in_use { "*" } else { " " },
+                    n.signal,
+                    if n.security.is_empty() { "open" } else { &n.security },
+                    n.ssid
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn tsv(&self) -> String {
+        self.networks
+            .iter()
+            .map(|n| {
+                format!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    n.ssid,
+                    n.bssid,
+                    n.signal,
+                    n.security,
+                    if n.in_use { "*" } else { "" }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// One access point from `--history`.
+#[derive(Serialize)]
+pub struct HistoryEntry {
+    pub bssid: String,
+    pub ssid: Ssid,
+    pub security: String,
+    pub best_signal: u8,
+    pub channel: u32,
+    /// RFC 3339, UTC
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+#[derive(Serialize)]
+pub struct HistoryReport {
+    /// Most recently seen first
+    pub networks: Vec<HistoryEntry>,
+}
+
+impl Report for HistoryReport {
+    fn text(&self) -> String {
+        self.networks
+            .iter()
+            .map(|n| {
+                format!(
+                    "{} {:>3}% {:<10} {} {} {}",
+                    n.bssid,
+                    n.best_signal,
+                    if n.security.is_empty() { "open" } else { &n.security },
+                    n.first_seen,
+                    n.last_seen,
+                    n.ssid
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn tsv(&self) -> String {
+        self.networks
+            .iter()
+            .map(|n| {
+                format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    n.bssid, n.ssid, n.security, n.best_signal, n.channel, n.first_seen, n.last_seen
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
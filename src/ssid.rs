@@ -0,0 +1,191 @@
+// SSIDs as the bytes that are actually broadcast.
+//
+// An SSID is up to 32 arbitrary bytes. Most are UTF-8, but Latin-1 names and
+// stray control characters are out there, and a lossy conversion to `String`
+// leaves nmcli unable to find the network again. We keep the bytes and only
+// escape them for display: bytes that aren't printable UTF-8 show as `\xNN`
+// and a backslash as `\\`, so the displayed form reads back to the same bytes.
+// That form is also what goes into JSON output and over the daemon socket.
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Ssid(Vec<u8>);
+
+impl Ssid {
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        Ssid(bytes.into())
+    }
+
+    /// Parses hex digits such as nmcli's SSID-HEX column, with or without "0x".
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix("0x").unwrap_or(hex);
+        if hex.is_empty() || !hex.len().is_multiple_of(2) {
+            return None;
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| hex.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .map(Ssid)
+    }
+
+    /// Reads back the escaped form `Display` writes.
+    pub fn parse_escaped(text: &str) -> Option<Self> {
+        let mut bytes = Vec::with_capacity(text.len());
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                continue;
+            }
+            match chars.next()? {
+                '\\' => bytes.push(b'\\'),
+                'x' => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    bytes.push(u8::from_str_radix(&hex, 16).ok().filter(|_| hex.len() == 2)?);
+                }
+                _ => return None,
+            }
+        }
+        Some(Ssid(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The SSID as text if it can be handed to nmcli as is: UTF-8 without
+    /// control characters.
+    pub fn as_text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok().filter(|text| !text.chars().any(char::is_control))
+    }
+
+    /// Name for the connection profile: the SSID itself when it's plain
+    /// text, the escaped form otherwise.
+    pub fn profile_name(&self) -> String {
+        self.as_text().map_or_else(|| self.to_string(), str::to_string)
+    }
+}
+
+impl From<&str> for Ssid {
+    fn from(text: &str) -> Self {
+        Ssid(text.as_bytes().to_vec())
+    }
+}
+
+impl fmt::Display for Ssid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.0.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                if c == '\\' {
+                    f.write_str("\\\\")?;
+                } else if c.is_control() {
+                    for b in c.encode_utf8(&mut [0; 4]).bytes() {
+                        write!(f, "\\x{:02X}", b)?;
+                    }
+                } else {
+                    write!(f, "{}", c)?;
+                }
+            }
+            for b in chunk.invalid() {
+                write!(f, "\\x{:02X}", b)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Ssid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Ssid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Ssid::parse_escaped(&text).ok_or_else(|| serde::de::Error::custom(format!("invalid SSID escape in '{}'", text)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_networks;
+
+    const ESCAPE: &[u8] = b"Esc\x1B[31mRed";
+    // "Café" in Latin-1
+    const LATIN1: &[u8] = b"Caf\xE9";
+
+    #[test]
+    fn from_hex() {
+        assert_eq!(Ssid::from_hex("436166E9"), Some(Ssid::from_bytes(LATIN1)));
+        assert_eq!(Ssid::from_hex("0x436166e9"), Some(Ssid::from_bytes(LATIN1)));
+        assert_eq!(Ssid::from_hex(""), None);
+        assert_eq!(Ssid::from_hex("436"), None);
+        assert_eq!(Ssid::from_hex("43zz"), None);
+        assert_eq!(Ssid::from_hex("4é"), None);
+    }
+
+    #[test]
+    fn display_escapes_what_is_not_printable_text() {
+        assert_eq!(Ssid::from_bytes(ESCAPE).to_string(), "Esc\\x1B[31mRed");
+        assert_eq!(Ssid::from_bytes(LATIN1).to_string(), "Caf\\xE9");
+        assert_eq!(Ssid::from("Back\\slash").to_string(), "Back\\\\slash");
+        assert_eq!(Ssid::from("Café").to_string(), "Café");
+        // Invalid UTF-8 in the middle of valid text
+        assert_eq!(Ssid::from_bytes(b"a\xFF\xFEb".to_vec()).to_string(), "a\\xFF\\xFEb");
+    }
+
+    #[test]
+    fn escaped_form_reads_back_to_the_same_bytes() {
+        for bytes in [ESCAPE, LATIN1, b"Back\\slash", "Café".as_bytes(), b"\\x41", b"a\xFF\xFEb", b""] {
+            let ssid = Ssid::from_bytes(bytes);
+            assert_eq!(Ssid::parse_escaped(&ssid.to_string()), Some(ssid));
+        }
+        let json = serde_json::to_string(&Ssid::from_bytes(LATIN1)).unwrap();
+        assert_eq!(json, "\"Caf\\\\xE9\"");
+        assert_eq!(serde_json::from_str::<Ssid>(&json).unwrap(), Ssid::from_bytes(LATIN1));
+    }
+
+    #[test]
+    fn parse_escaped_rejects_broken_escapes() {
+        assert_eq!(Ssid::parse_escaped("trailing\\"), None);
+        assert_eq!(Ssid::parse_escaped("\\q"), None);
+        assert_eq!(Ssid::parse_escaped("\\x4"), None);
+        assert_eq!(Ssid::parse_escaped("\\xé1"), None);
+    }
+
+    #[test]
+    fn only_plain_text_goes_to_nmcli_as_is() {
+        assert_eq!(Ssid::from("Café").as_text(), Some("Café"));
+        assert_eq!(Ssid::from("Café").profile_name(), "Café");
+        assert_eq!(Ssid::from_bytes(ESCAPE).as_text(), None);
+        assert_eq!(Ssid::from_bytes(ESCAPE).profile_name(), "Esc\\x1B[31mRed");
+        assert_eq!(Ssid::from_bytes(LATIN1).as_text(), None);
+        assert_eq!(Ssid::from_bytes(LATIN1).profile_name(), "Caf\\xE9");
+    }
+
+    #[test]
+    fn parse_networks_prefers_the_hex_column() {
+        // nmcli's text column turns the Latin-1 byte into U+FFFD and the
+        // escape into a literal character; the hex column has the real bytes
+        let output = "\
+ :Caf\u{FFFD}:AA\\:BB\\:CC\\:DD\\:EE\\:01:WPA2:70:2412 MHz:1:/ap/1:436166E9
+ :Esc\u{1B}[31mRed:AA\\:BB\\:CC\\:DD\\:EE\\:02::50:2437 MHz:6:/ap/2:4573631B5B33316D526564
+ :Old\\:nmcli:AA\\:BB\\:CC\\:DD\\:EE\\:03::40:2462 MHz:11:/ap/3
+";
+        let ssids: Vec<Ssid> = parse_networks(output).into_iter().map(|n| n.ssid).collect();
+        assert_eq!(
+            ssids,
+            [Ssid::from_bytes(LATIN1), Ssid::from_bytes(ESCAPE), Ssid::from("Old:nmcli")]
+        );
+    }
+}
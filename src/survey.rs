@@ -0,0 +1,326 @@
+// Site survey recording.
+//
+// With `--survey <file>` the TUI scans every `survey.interval` seconds and
+// appends one record per access point to the file: CSV when the name ends in
+// `.csv`, JSON Lines otherwise. A waypoint is a record of its own and also
+// becomes the location of the observations that follow, until the next one.
+// The file is only ever appended to, so a survey can be resumed.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::ssid::Ssid;
+use crate::{unix_now, utc_timestamp, Network};
+
+const CSV_HEADER: &str = "timestamp,type,interface,ssid,bssid,channel,frequency,signal,security,location";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Csv,
+    JsonLines,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    timestamp: &'a str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    interface: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ssid: Option<&'a Ssid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bssid: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    channel: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signal: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    security: Option<&'a str>,
+    location: Option<&'a str>,
+}
+
+impl Record<'_> {
+    fn csv_line(&self) -> String {
+        let ssid = self.ssid.map(Ssid::to_string);
+        let number = |n: Option<u32>| n.map(|n| n.to_string()).unwrap_or_default();
+        [
+            self.timestamp.to_string(),
+            self.kind.to_string(),
+            self.interface.to_string(),
+            ssid.unwrap_or_default(),
+            self.bssid.unwrap_or_default().to_string(),
+            number(self.channel),
+            number(self.frequency),
+            number(self.signal.map(u32::from)),
+            self.security.unwrap_or_default().to_string(),
+            self.location.unwrap_or_default().to_string(),
+        ]
+        .iter()
+        .map(|field| csv_field(field))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+}
+
+pub struct Survey {
+    path: PathBuf,
+    file: File,
+    format: Format,
+    interval: Duration,
+    last_scan: Option<Instant>,
+    location: Option<String>,
+    observations: u64,
+}
+
+impl Survey {
+    /// Opens `path` for appending, writing the CSV header if the file is new.
+    pub fn open(path: &str, interval: Duration) -> Result<Self, Error> {
+        let path = PathBuf::from(path);
+        let format = match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Format::Csv,
+            _ => Format::JsonLines,
+        };
+        let io_error = |source| Error::Io {
+            context: format!("Cannot write survey to {}", path.display()),
+            source,
+        };
+        let mut file = OpenOptions::new().create(true).append(true).open(&path).map_err(io_error)?;
+        let empty = file.metadata().map_err(io_error)?.len() == 0;
+        if format == Format::Csv && empty {
+            writeln!(file, "{}", CSV_HEADER).map_err(io_error)?;
+        }
+        Ok(Self {
+            path,
+            file,
+            format,
+            interval,
+            last_scan: None,
+            location: None,
+            observations: 0,
+        })
+    }
+
+    /// A scan is due right away, then every interval.
+    pub fn is_due(&self) -> bool {
+        self.last_scan.is_none_or(|last| last.elapsed() >= self.interval)
+    }
+
+    /// Logs every access point of one scan under the same timestamp.
+    pub fn record_scan(&mut self, interface: &str, access_points: &[Network]) -> Result<(), Error> {
+        self.last_scan = Some(Instant::now());
+        let timestamp = utc_timestamp(unix_now());
+        let mut lines = String::new();
+        for ap in access_points {
+            let record = Record {
+                timestamp: &timestamp,
+                kind: "observation",
+                interface,
+                ssid: Some(&ap.ssid),
+                bssid: Some(&ap.bssid),
+                channel: Some(ap.chan),
+                frequency: Some(ap.freq),
+                signal: Some(ap.signal),
+                security: Some(&ap.security),
+                location: self.location.as_deref(),
+            };
+            lines.push_str(&self.line(&record));
+        }
+        self.write(&lines)?;
+        self.observations += access_points.len() as u64;
+        Ok(())
+    }
+
+    /// Logs a waypoint and tags the following observations with its name.
+    /// An empty name only clears the location.
+    pub fn waypoint(&mut self, interface: &str, name: &str) -> Result<(), Error> {
+        let name = name.trim();
+        if name.is_empty() {
+            self.location = None;
+            return Ok(());
+        }
+        let timestamp = utc_timestamp(unix_now());
+        let record = Record {
+            timestamp: &timestamp,
+            kind: "waypoint",
+            interface,
+            ssid: None,
+            bssid: None,
+            channel: None,
+            frequency: None,
+            signal: None,
+            security: None,
+            location: Some(name),
+        };
+        let line = self.line(&record);
+        self.write(&line)?;
+        self.location = Some(name.to_string());
+        Ok(())
+    }
+
+    /// "REC 152 @ lobby" for the status bar.
+    pub fn status(&self) -> String {
+        match &self.location {
+            Some(location) => format!("REC {} @ {}", self.observations, location),
+            None => format!("REC {}", self.observations),
+        }
+    }
+
+    fn line(&self, record: &Record) -> String {
+        match self.format {
+            Format::Csv => format!("{}\n", record.csv_line()),
+            Format::JsonLines => format!("{}\n", serde_json::to_string(record).unwrap_or_default()),
+        }
+    }
+
+    // One write per scan, so an interrupted survey doesn't end mid-scan
+    fn write(&mut self, lines: &str) -> Result<(), Error> {
+        self.file
+            .write_all(lines.as_bytes())
+            .and_then(|()| self.file.flush())
+            .map_err(|source: io::Error| Error::Io {
+                context: format!("Cannot write survey to {}", self.path.display()),
+                source,
+            })
+    }
+}
+
+/// Quotes a field if it holds a comma, quote or line break (RFC 4180).
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_access_points;
+    use serde_json::json;
+    use std::fs;
+
+    fn observation<'a>(ssid: &'a Ssid, location: Option<&'a str>) -> Record<'a> {
+        Record {
+            timestamp: "2026-05-04T10:00:00Z",
+            kind: "observation",
+            interface: "wlan0",
+            ssid: Some(ssid),
+            bssid: Some("AA:BB:CC:DD:EE:01"),
+            channel: Some(6),
+            frequency: Some(2437),
+            signal: Some(82),
+            security: Some("WPA1 WPA2"),
+            location,
+        }
+    }
+
+    #[test]
+    fn quotes_csv_fields_only_when_needed() {
+        let cases = [
+            ("Home", "Home"),
+            ("", ""),
+            ("Cafe, Bar", "\"Cafe, Bar\""),
+            ("The \"Best\" Wi-Fi", "\"The \"\"Best\"\" Wi-Fi\""),
+            ("two\nlines", "\"two\nlines\""),
+            ("cr\r", "\"cr\r\""),
+            ("Café 日本", "Café 日本"),
+        ];
+        for (field, quoted) in cases {
+            assert_eq!(csv_field(field), quoted);
+        }
+    }
+
+    #[test]
+    fn csv_line_has_a_field_per_header_column() {
+        let ssid = Ssid::from("Lobby, \"Guest\"");
+        let line = observation(&ssid, Some("2nd floor, east")).csv_line();
+        assert_eq!(
+            line,
+            "2026-05-04T10:00:00Z,observation,wlan0,\"Lobby, \"\"Guest\"\"\",AA:BB:CC:DD:EE:01,6,2437,82,WPA1 WPA2,\"2nd floor, east\""
+        );
+
+        // A line break in an SSID is escaped before it gets here, so rows never span lines
+        let ssid = Ssid::from_bytes(b"Line\nBreak".to_vec());
+        let line = observation(&ssid, None).csv_line();
+        assert_eq!(line, "2026-05-04T10:00:00Z,observation,wlan0,Line\\x0ABreak,AA:BB:CC:DD:EE:01,6,2437,82,WPA1 WPA2,");
+        assert_eq!(CSV_HEADER.split(',').count(), 10);
+    }
+
+    #[test]
+    fn json_records_have_a_stable_shape() {
+        let ssid = Ssid::from_bytes(b"Caf\xE9".to_vec());
+        let value = serde_json::to_value(observation(&ssid, None)).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "timestamp": "2026-05-04T10:00:00Z",
+                "type": "observation",
+                "interface": "wlan0",
+                "ssid": "Caf\\xE9",
+                "bssid": "AA:BB:CC:DD:EE:01",
+                "channel": 6,
+                "frequency": 2437,
+                "signal": 82,
+                "security": "WPA1 WPA2",
+                "location": null,
+            })
+        );
+
+        let waypoint = Record {
+            timestamp: "2026-05-04T10:00:00Z",
+            kind: "waypoint",
+            interface: "wlan0",
+            ssid: None,
+            bssid: None,
+            channel: None,
+            frequency: None,
+            signal: None,
+            security: None,
+            location: Some("lobby"),
+        };
+        assert_eq!(
+            serde_json::to_value(waypoint).unwrap(),
+            json!({ "timestamp": "2026-05-04T10:00:00Z", "type": "waypoint", "interface": "wlan0", "location": "lobby" })
+        );
+    }
+
+    #[test]
+    fn appends_to_a_csv_file_with_one_header() {
+        let path = std::env::temp_dir().join(format!("wifi_menu-survey-{}.csv", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let aps = parse_access_points(
+            "*:Home, Net:AA\\:BB\\:CC\\:DD\\:EE\\:01:WPA2:82:2437 MHz:6:/ap/1\n\
+             :Open Cafe:AA\\:BB\\:CC\\:DD\\:EE\\:02::40:2412 MHz:1:/ap/2\n",
+        );
+
+        let mut survey = Survey::open(path.to_str().unwrap(), Duration::from_secs(10)).unwrap();
+        survey.record_scan("wlan0", &aps).unwrap();
+        survey.waypoint("wlan0", " lobby ").unwrap();
+        drop(survey);
+        // Resuming appends without a second header
+        let mut survey = Survey::open(path.to_str().unwrap(), Duration::from_secs(10)).unwrap();
+        survey.waypoint("wlan0", "lobby").unwrap();
+        survey.record_scan("wlan0", &aps[..1]).unwrap();
+        assert_eq!(survey.status(), "REC 1 @ lobby");
+
+        let text = fs::read_to_string(&path).unwrap();
+        let rows: Vec<&str> = text.lines().collect();
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0], CSV_HEADER);
+        // Drop the timestamps
+        let rest = |row: &str| row.split_once(',').unwrap().1.to_string();
+        assert_eq!(rest(rows[1]), "observation,wlan0,\"Home, Net\",AA:BB:CC:DD:EE:01,6,2437,82,WPA2,");
+        assert_eq!(rest(rows[2]), "observation,wlan0,Open Cafe,AA:BB:CC:DD:EE:02,1,2412,40,,");
+        assert_eq!(rest(rows[3]), "waypoint,wlan0,,,,,,,lobby");
+        assert_eq!(rest(rows[5]), "observation,wlan0,\"Home, Net\",AA:BB:CC:DD:EE:01,6,2437,82,WPA2,lobby");
+        fs::remove_file(path).unwrap();
+    }
+}
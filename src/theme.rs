@@ -0,0 +1,210 @@
+// Colors and icons.
+//
+// The TUI takes its colors from a theme. The TUI, the launcher menu and the
+// status bar output all take their glyphs from an icon set. Both come from the
+// config file or `--theme`/`--icons`. With `NO_COLOR` set and no theme chosen,
+// the monochrome theme is used (https://no-color.org).
+
+use std::env;
+use std::sync::OnceLock;
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::bar;
+
+use crate::config::{self, IconSet, Theme};
+
+pub struct Palette {
+    /// Row of the network in use
+    pub active: Style,
+    /// Selected row of the network list
+    pub selected: Style,
+    pub status: Style,
+    /// Status bar while something is running
+    pub busy: Style,
+    /// Status bar while a message waits for a key press
+    pub message: Style,
+    pub popup: Style,
+    pub error: Style,
+    /// Advice that isn't an error yet
+    pub hint: Style,
+    /// Selected entry of the action menu
+    pub menu_selected: Style,
+    /// Characters of an SSID that match the search
+    pub matched: Style,
+    /// Active filters above the network list
+    pub chip: Style,
+}
+
+const DARK: Palette = Palette {
+    active: Style::new().fg(Color::Green),
+    selected: Style::new().bg(Color::DarkGray).add_modifier(Modifier::BOLD),
+    status: Style::new().bg(Color::White).fg(Color::Black),
+    busy: Style::new().bg(Color::Yellow).fg(Color::Black),
+    message: Style::new().bg(Color::Blue),
+    popup: Style::new().fg(Color::Yellow),
+    error: Style::new().fg(Color::Red),
+    hint: Style::new().fg(Color::DarkGray),
+    menu_selected: Style::new().bg(Color::Red).fg(Color::White),
+    matched: Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+    chip: Style::new().bg(Color::Cyan).fg(Color::Black),
+};
+
+// Yellow and light gray vanish on a white background
+const LIGHT: Palette = Palette {
+    active: Style::new().fg(Color::Green).add_modifier(Modifier::BOLD),
+    selected: Style::new().bg(Color::Gray).fg(Color::Black).add_modifier(Modifier::BOLD),
+    status: Style::new().bg(Color::Black).fg(Color::White),
+    busy: Style::new().bg(Color::Yellow).fg(Color::Black),
+    message: Style::new().bg(Color::Blue).fg(Color::White),
+    popup: Style::new().fg(Color::Blue),
+    error: Style::new().fg(Color::Red),
+    hint: Style::new().fg(Color::DarkGray),
+    menu_selected: Style::new().bg(Color::Red).fg(Color::White),
+    matched: Style::new().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+    chip: Style::new().bg(Color::Cyan).fg(Color::Black),
+};
+
+const HIGH_CONTRAST: Palette = Palette {
+    active: Style::new().fg(Color::LightGreen).add_modifier(Modifier::BOLD),
+    selected: Style::new().bg(Color::White).fg(Color::Black).add_modifier(Modifier::BOLD),
+    status: Style::new().bg(Color::White).fg(Color::Black),
+    busy: Style::new().bg(Color::LightYellow).fg(Color::Black).add_modifier(Modifier::BOLD),
+    message: Style::new().bg(Color::LightCyan).fg(Color::Black),
+    popup: Style::new().fg(Color::White),
+    error: Style::new().fg(Color::LightRed).add_modifier(Modifier::BOLD),
+    hint: Style::new().fg(Color::White),
+    menu_selected: Style::new().bg(Color::White).fg(Color::Black).add_modifier(Modifier::BOLD),
+    matched: Style::new().fg(Color::LightYellow).add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED),
+    chip: Style::new().bg(Color::LightCyan).fg(Color::Black).add_modifier(Modifier::BOLD),
+};
+
+// Attributes only, so it follows the terminal's own colors
+const MONOCHROME: Palette = Palette {
+    active: Style::new().add_modifier(Modifier::BOLD),
+    selected: Style::new().add_modifier(Modifier::REVERSED),
+    status: Style::new().add_modifier(Modifier::REVERSED),
+    busy: Style::new().add_modifier(Modifier::REVERSED).add_modifier(Modifier::BOLD),
+    message: Style::new().add_modifier(Modifier::REVERSED),
+    popup: Style::new(),
+    error: Style::new().add_modifier(Modifier::BOLD),
+    hint: Style::new().add_modifier(Modifier::DIM),
+    menu_selected: Style::new().add_modifier(Modifier::REVERSED),
+    matched: Style::new().add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED),
+    chip: Style::new().add_modifier(Modifier::REVERSED),
+};
+
+pub struct Icons {
+    /// Signal strength, weakest first; one step per `ui.signal_thresholds` entry
+    pub signal: [&'static str; 5],
+    /// Marks the network in use; `inactive` is the same width
+    pub active: &'static str,
+    pub inactive: &'static str,
+    /// Secured networks; `open` is the same width
+    pub lock: &'static str,
+    pub open: &'static str,
+    pub busy: &'static str,
+    /// The rescan entry of the dmenu frontend
+    pub rescan: &'static str,
+    pub message: &'static str,
+    /// Status bar output without a connection
+    pub disconnected: &'static str,
+    /// Next to the sort key in the list title: normal and reversed order
+    pub sorted: &'static str,
+    pub reversed: &'static str,
+    /// Bars of the signal history in the details pane
+    pub sparkline: bar::Set,
+    /// Outline of an access point in the channel view: left and right
+    /// corner, top edge, sides
+    pub channel: [&'static str; 4],
+}
+
+const NERD: Icons = Icons {
+    signal: ["\u{f092f}", "\u{f091f}", "\u{f0922}", "\u{f0925}", "\u{f0928}"],
+    active: "\u{f00c} ",
+    inactive: "  ",
+    lock: "\u{f023}",
+    open: " ",
+    busy: "\u{f0450}",
+    rescan: "\u{f0450}",
+    message: "\u{f02d7}",
+    disconnected: "\u{f092e}",
+    sorted: "\u{f0045}",
+    reversed: "\u{f005d}",
+    sparkline: bar::NINE_LEVELS,
+    channel: ["╭", "╮", "─", "│"],
+};
+
+// Block elements and symbols that ship with the usual monospace fonts
+const UNICODE: Icons = Icons {
+    signal: ["▁___", "▂___", "▂▄__", "▂▄▆_", "▂▄▆█"],
+    active: "● ",
+    inactive: "  ",
+    lock: "⚷",
+    open: " ",
+    busy: "…",
+    rescan: "↻",
+    message: "ℹ",
+    disconnected: "✗",
+    sorted: "↓",
+    reversed: "↑",
+    sparkline: bar::NINE_LEVELS,
+    channel: ["╭", "╮", "─", "│"],
+};
+
+const ASCII: Icons = Icons {
+    signal: ["[    ]", "[#   ]", "[##  ]", "[### ]", "[####]"],
+    active: "* ",
+    inactive: "  ",
+    lock: "L",
+    open: " ",
+    busy: "~",
+    rescan: "~",
+    message: "!",
+    disconnected: "[----]",
+    sorted: "v",
+    reversed: "^",
+    sparkline: bar::Set {
+        full: "#",
+        seven_eighths: "#",
+        three_quarters: "=",
+        five_eighths: "=",
+        half: "-",
+        three_eighths: "-",
+        one_quarter: "_",
+        one_eighth: "_",
+        empty: " ",
+    },
+    channel: ["+", "+", "-", "|"],
+};
+
+static SELECTED: OnceLock<(Theme, IconSet)> = OnceLock::new();
+
+/// Picks the theme and icon set once at startup. Command line choices beat
+/// the config file, which beats `NO_COLOR`.
+pub fn init(theme: Option<Theme>, icons: Option<IconSet>) {
+    let ui = &config::get().ui;
+    let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let theme = theme.or(ui.theme).unwrap_or(if no_color { Theme::Monochrome } else { Theme::Dark });
+    let _ = SELECTED.set((theme, icons.unwrap_or(ui.icons)));
+}
+
+fn selected() -> (Theme, IconSet) {
+    *SELECTED.get_or_init(|| (config::get().ui.theme.unwrap_or_default(), config::get().ui.icons))
+}
+
+pub fn palette() -> &'static Palette {
+    match selected().0 {
+        Theme::Dark => &DARK,
+        Theme::Light => &LIGHT,
+        Theme::HighContrast => &HIGH_CONTRAST,
+        Theme::Monochrome => &MONOCHROME,
+    }
+}
+
+pub fn icons() -> &'static Icons {
+    match selected().1 {
+        IconSet::Nerd => &NERD,
+        IconSet::Unicode => &UNICODE,
+        IconSet::Ascii => &ASCII,
+    }
+}
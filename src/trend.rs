@@ -0,0 +1,77 @@
+// Signal strength over time, per access point.
+//
+// Each scan the TUI receives adds one sample for every BSSID in it, so an AP
+// that flaps shows up even while standing still. The most recent samples are
+// kept for the sparkline; min/avg/max cover the whole session. Nothing is
+// written to disk.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::Network;
+
+/// Samples kept per access point; enough for a full-width sparkline.
+const SAMPLES: usize = 120;
+
+#[derive(Default)]
+pub struct Trend {
+    samples: VecDeque<u8>,
+    min: u8,
+    max: u8,
+    sum: u64,
+    count: u64,
+}
+
+impl Trend {
+    fn push(&mut self, signal: u8) {
+        if self.samples.len() == SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(signal);
+        if self.count == 0 {
+            (self.min, self.max) = (signal, signal);
+        }
+        self.min = self.min.min(signal);
+        self.max = self.max.max(signal);
+        self.sum += u64::from(signal);
+        self.count += 1;
+    }
+
+    /// Up to `n` of the latest samples, oldest first.
+    pub fn recent(&self, n: usize) -> Vec<u64> {
+        let skip = self.samples.len().saturating_sub(n);
+        self.samples.iter().skip(skip).map(|&s| u64::from(s)).collect()
+    }
+
+    pub fn min(&self) -> u8 {
+        self.min
+    }
+
+    pub fn max(&self) -> u8 {
+        self.max
+    }
+
+    pub fn avg(&self) -> u8 {
+        self.sum.checked_div(self.count).unwrap_or(0) as u8
+    }
+
+    /// Scans the AP was seen in this session.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+#[derive(Default)]
+pub struct Trends(HashMap<String, Trend>);
+
+impl Trends {
+    /// Adds a sample for every access point in one scan.
+    pub fn record(&mut self, networks: &[Network]) {
+        for net in networks.iter().filter(|n| !n.bssid.is_empty()) {
+            self.0.entry(net.bssid.clone()).or_default().push(net.signal);
+        }
+    }
+
+    pub fn get(&self, bssid: &str) -> Option<&Trend> {
+        self.0.get(bssid)
+    }
+}